@@ -0,0 +1,165 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{SnowflakeError, error};
+
+#[cfg(test)]
+#[path = "./account_test.rs"]
+mod account_test;
+
+static DOMAIN: &str = "snowflakecomputing.com";
+static CHINA_DOMAIN: &str = "snowflakecomputing.cn";
+static PRIVATELINK: &str = "privatelink";
+static CHINA_REGION_PREFIX: &str = "cn-";
+
+/// A Snowflake account identifier, as passed to `account_id`.
+///
+/// Understands the forms Snowflake hands out:
+/// - `org-account`, the preferred organization and account name
+/// - `locator`, `locator.region` or `locator.region.cloud`, the legacy account locator
+/// - any of the above followed by `.privatelink` for AWS PrivateLink or Azure Private Link
+/// - the full hostname, e.g. `xy12345.us-east-2.aws.snowflakecomputing.com`
+///
+/// Accounts in China (`cn-` regions or a `.snowflakecomputing.cn` hostname) use the `.cn` domain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountIdentifier {
+    raw: String,
+    name: String,
+    region: Option<String>,
+    privatelink: bool,
+    china: bool,
+}
+
+impl AccountIdentifier {
+    pub fn parse(identifier: &str) -> Result<Self, SnowflakeError> {
+        let raw = identifier.trim();
+        if raw.is_empty() {
+            return Err(error!("account identifier is empty"));
+        }
+
+        if let Some(c) = raw
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && !matches!(c, '-' | '_' | '.'))
+        {
+            return Err(error!(
+                "account identifier may only contain letters, digits, '-', '_' and '.', set host for a full url",
+                c
+            ));
+        }
+
+        let lower = raw.to_ascii_lowercase();
+        let (rest, mut china) = if let Some(rest) = strip_domain(&lower, DOMAIN) {
+            (&raw[..rest.len()], false)
+        } else if let Some(rest) = strip_domain(&lower, CHINA_DOMAIN) {
+            (&raw[..rest.len()], true)
+        } else {
+            (raw, false)
+        };
+
+        let mut segments: Vec<&str> = rest.split('.').collect();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(error!("account identifier has an empty segment", raw));
+        }
+
+        let privatelink = segments
+            .last()
+            .is_some_and(|last| last.eq_ignore_ascii_case(PRIVATELINK));
+        if privatelink {
+            segments.pop();
+        }
+
+        let name = segments.remove(0);
+        if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return Err(error!(
+                "account name must start with a letter or digit",
+                raw
+            ));
+        }
+
+        let region = match segments.is_empty() {
+            true => None,
+            false => Some(segments.join(".")),
+        };
+
+        if region
+            .as_deref()
+            .is_some_and(|region| region.to_ascii_lowercase().starts_with(CHINA_REGION_PREFIX))
+        {
+            china = true;
+        }
+
+        Ok(Self {
+            raw: raw.to_string(),
+            name: name.to_string(),
+            region,
+            privatelink,
+            china,
+        })
+    }
+
+    /// The account name or locator without any region, as sent in the login request.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The region (and cloud) of a legacy account locator, e.g. `us-east-2.aws`.
+    pub fn region(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    pub fn is_privatelink(&self) -> bool {
+        self.privatelink
+    }
+
+    pub fn is_china(&self) -> bool {
+        self.china
+    }
+
+    /// The hostname of the account's Snowflake endpoint.
+    ///
+    /// Underscores in the account name are replaced with hyphens, since they aren't valid in hostnames.
+    pub fn host(&self) -> String {
+        let mut host = self.name.replace('_', "-");
+
+        if let Some(region) = &self.region {
+            host.push('.');
+            host.push_str(region);
+        }
+        if self.privatelink {
+            host.push('.');
+            host.push_str(PRIVATELINK);
+        }
+
+        host.push('.');
+        host.push_str(if self.china { CHINA_DOMAIN } else { DOMAIN });
+        host.to_ascii_lowercase()
+    }
+
+    /// The `https://` url of the account's Snowflake endpoint.
+    pub fn base_url(&self) -> String {
+        format!("https://{}", self.host())
+    }
+
+    /// The account as it appears in the `iss` and `sub` claims of a key pair JWT.
+    /// Snowflake expects it uppercase and without the region of a legacy locator.
+    pub fn jwt_account_name(&self) -> String {
+        self.name.to_ascii_uppercase()
+    }
+}
+
+fn strip_domain<'a>(identifier: &'a str, domain: &str) -> Option<&'a str> {
+    identifier.strip_suffix(domain)?.strip_suffix('.')
+}
+
+impl FromStr for AccountIdentifier {
+    type Err = SnowflakeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for AccountIdentifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
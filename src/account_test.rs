@@ -0,0 +1,95 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_org_account() {
+    let account = AccountIdentifier::parse("myorg-my_account").unwrap();
+
+    assert_eq!(account.name(), "myorg-my_account");
+    assert_eq!(account.region(), None);
+    assert_eq!(account.host(), "myorg-my-account.snowflakecomputing.com");
+    assert_eq!(account.jwt_account_name(), "MYORG-MY_ACCOUNT");
+}
+
+#[test]
+fn test_legacy_locator() {
+    let account = AccountIdentifier::parse("xy12345.us-east-2.aws").unwrap();
+
+    assert_eq!(account.name(), "xy12345");
+    assert_eq!(account.region(), Some("us-east-2.aws"));
+    assert_eq!(
+        account.base_url(),
+        "https://xy12345.us-east-2.aws.snowflakecomputing.com"
+    );
+    assert_eq!(account.jwt_account_name(), "XY12345");
+}
+
+#[test]
+fn test_privatelink() {
+    let account = AccountIdentifier::parse("xy12345.eu-west-1.privatelink").unwrap();
+
+    assert!(account.is_privatelink());
+    assert_eq!(account.region(), Some("eu-west-1"));
+    assert_eq!(
+        account.host(),
+        "xy12345.eu-west-1.privatelink.snowflakecomputing.com"
+    );
+}
+
+#[test]
+fn test_china() {
+    let account = AccountIdentifier::parse("xy12345.cn-northwest-1").unwrap();
+    assert!(account.is_china());
+    assert_eq!(
+        account.host(),
+        "xy12345.cn-northwest-1.snowflakecomputing.cn"
+    );
+
+    let account = AccountIdentifier::parse("myorg-acct.snowflakecomputing.cn").unwrap();
+    assert!(account.is_china());
+    assert_eq!(account.name(), "myorg-acct");
+    assert_eq!(account.host(), "myorg-acct.snowflakecomputing.cn");
+}
+
+#[test]
+fn test_full_hostname() {
+    let account =
+        AccountIdentifier::parse("XY12345.us-east-1.privatelink.snowflakecomputing.com").unwrap();
+
+    assert_eq!(account.name(), "XY12345");
+    assert_eq!(account.region(), Some("us-east-1"));
+    assert!(account.is_privatelink());
+    assert_eq!(
+        account.host(),
+        "xy12345.us-east-1.privatelink.snowflakecomputing.com"
+    );
+}
+
+#[test]
+fn test_rejects_invalid_identifiers() {
+    for identifier in [
+        "",
+        "https://myorg-acct.snowflakecomputing.com",
+        "myorg acct",
+        "xy12345..aws",
+        "-myorg",
+    ] {
+        assert!(
+            AccountIdentifier::parse(identifier).is_err(),
+            "{identifier:?} should be rejected"
+        );
+    }
+}
+
+#[test]
+fn test_builder_validates_account_id() {
+    let err = crate::SnowflakeConnectionOptsBuilder::default()
+        .account_id("myorg/acct")
+        .username("alice")
+        .strategy(crate::auth::AuthStrategy::Password("hunter2".into()))
+        .pool_size(1)
+        .build()
+        .expect_err("an invalid account id should fail to build");
+
+    assert!(err.to_string().contains("account identifier"));
+}
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, fmt::Debug};
+
+use futures_util::future::BoxFuture;
+use serde_json::{Map, Value};
+
+#[cfg(test)]
+#[path = "./authenticator_test.rs"]
+mod authenticator_test;
+
+pub type AuthenticatorError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Adds credentials to the login request, and optionally reacts to how Snowflake answered it.
+///
+/// Implement this for auth methods this crate doesn't support out of the box, and set it with
+/// `SnowflakeConnectionOptsBuilder::authenticator`. `authenticate` is called on every login,
+/// including the re-login done once the master token expires.
+///
+/// ```ignore
+/// #[derive(Debug)]
+/// struct Vault;
+///
+/// impl Authenticator for Vault {
+///     fn authenticate<'a>(
+///         &'a self,
+///         request: &'a mut LoginRequest,
+///     ) -> BoxFuture<'a, Result<(), AuthenticatorError>> {
+///         Box::pin(async move {
+///             let password = fetch_password_from_vault(request.username()).await?;
+///             request.set("PASSWORD", password);
+///             Ok(())
+///         })
+///     }
+/// }
+/// ```
+pub trait Authenticator: Send + Sync + Debug {
+    fn authenticate<'a>(
+        &'a self,
+        request: &'a mut LoginRequest,
+    ) -> BoxFuture<'a, Result<(), AuthenticatorError>>;
+
+    /// Called with every login response. Returning `LoginAction::Retry` after a failed login
+    /// calls `authenticate` again with a fresh request, up to a few times.
+    fn on_response(&self, _response: &AuthResponse) -> LoginAction {
+        LoginAction::Continue
+    }
+}
+
+impl<T: Authenticator + ?Sized> Authenticator for &T {
+    fn authenticate<'a>(
+        &'a self,
+        request: &'a mut LoginRequest,
+    ) -> BoxFuture<'a, Result<(), AuthenticatorError>> {
+        (**self).authenticate(request)
+    }
+
+    fn on_response(&self, response: &AuthResponse) -> LoginAction {
+        (**self).on_response(response)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoginAction {
+    /// Accept the response, failing the login if it was unsuccessful.
+    Continue,
+    /// Log in again from scratch.
+    Retry,
+}
+
+/// What Snowflake answered a login request with.
+#[derive(Clone, Debug)]
+pub struct AuthResponse {
+    pub success: bool,
+    pub code: Option<String>,
+    pub message: Option<String>,
+    /// What Snowflake expects the client to do next, e.g. `RETRY_LOGIN` or `EXT_AUTHN_DUO_ALL`.
+    pub next_action: Option<String>,
+    pub authn_method: Option<String>,
+}
+
+/// The login request about to be sent to `/session/v1/login-request`.
+///
+/// `Debug` leaves out the body and header values, since they hold the credentials.
+pub struct LoginRequest {
+    account_id: String,
+    username: String,
+    body: Value,
+    headers: HashMap<String, String>,
+}
+
+impl LoginRequest {
+    pub(crate) fn new(
+        account_id: &str,
+        username: &str,
+        body: Value,
+        headers: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            username: username.to_string(),
+            body,
+            headers,
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> (Value, HashMap<String, String>) {
+        (self.body, self.headers)
+    }
+
+    /// The account name, without the region of a legacy account locator.
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// The `data` object of the body, where fields like `AUTHENTICATOR` and `TOKEN` go.
+    pub fn data(&self) -> &Map<String, Value> {
+        self.body["data"]
+            .as_object()
+            .expect("unexpected none object in login_body")
+    }
+
+    pub fn data_mut(&mut self) -> &mut Map<String, Value> {
+        self.body["data"]
+            .as_object_mut()
+            .expect("unexpected none object in login_body")
+    }
+
+    /// Sets a field of the body's `data` object.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.data_mut().insert(key.into(), value.into());
+    }
+
+    /// Sets a session parameter, sent as part of `SESSION_PARAMETERS`.
+    pub fn set_session_parameter(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        let params = self
+            .data_mut()
+            .entry("SESSION_PARAMETERS")
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        if let Some(params) = params.as_object_mut() {
+            params.insert(key.into(), value.into());
+        }
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HashMap<String, String> {
+        &mut self.headers
+    }
+
+    pub fn set_header(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.headers.insert(key.into(), value.into());
+    }
+}
+
+impl Debug for LoginRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginRequest")
+            .field("account_id", &self.account_id)
+            .field("username", &self.username)
+            .field("data", &self.data().keys().collect::<Vec<_>>())
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
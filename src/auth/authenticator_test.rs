@@ -0,0 +1,151 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use futures_util::FutureExt;
+use serde_json::json;
+
+#[cfg(test)]
+use super::*;
+use crate::{
+    auth::AuthStrategy,
+    test_utils::{
+        MockResponse, MockServer, TestClient, login_failure, login_success, test_opts_builder,
+    },
+};
+
+/// Logs in with a one-time code, asking for a new code whenever Snowflake says to retry.
+#[derive(Debug, Default)]
+struct OneTimeCode {
+    issued: AtomicUsize,
+}
+
+impl Authenticator for OneTimeCode {
+    fn authenticate<'a>(
+        &'a self,
+        request: &'a mut LoginRequest,
+    ) -> BoxFuture<'a, Result<(), AuthenticatorError>> {
+        async move {
+            let code = self.issued.fetch_add(1, Ordering::SeqCst) + 1;
+
+            request.set("AUTHENTICATOR", "CUSTOM_CODE");
+            request.set("TOKEN", format!("code-{code}"));
+            request.set_session_parameter("CUSTOM_PARAMETER", "set-by-authenticator");
+            request.set_header("X-Custom-Auth", request.username().to_string());
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn on_response(&self, response: &AuthResponse) -> LoginAction {
+        match response.next_action.as_deref() {
+            Some("RETRY_LOGIN") => LoginAction::Retry,
+            _ => LoginAction::Continue,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FailingAuthenticator;
+
+impl Authenticator for FailingAuthenticator {
+    fn authenticate<'a>(
+        &'a self,
+        _request: &'a mut LoginRequest,
+    ) -> BoxFuture<'a, Result<(), AuthenticatorError>> {
+        async move { Err("vault is sealed".into()) }.boxed()
+    }
+}
+
+fn mock_snowflake() -> MockServer {
+    MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => {
+            if req.json()["data"]["TOKEN"] == "code-2" {
+                login_success()
+            } else {
+                MockResponse::json(json!({
+                    "data": { "nextAction": "RETRY_LOGIN", "authnMethod": "CUSTOM_CODE" },
+                    "code": "390100",
+                    "message": "Code has already been used.",
+                    "success": false
+                }))
+            }
+        }
+        _ => MockResponse::status(404),
+    })
+}
+
+#[tokio::test]
+async fn test_custom_authenticator_retries_on_next_action() {
+    let snowflake = mock_snowflake();
+
+    let opts = test_opts_builder(&snowflake.url(), AuthStrategy::Password("unused".into()))
+        .authenticator(OneTimeCode::default())
+        .build()
+        .unwrap();
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in with a custom authenticator");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 2);
+
+    let data = &logins[1].json()["data"];
+    assert_eq!(data["AUTHENTICATOR"], "CUSTOM_CODE");
+    assert_eq!(data["TOKEN"], "code-2");
+    assert_eq!(data["ACCOUNT_NAME"], "TEST_ACCOUNT");
+    assert!(data.get("PASSWORD").is_none());
+    assert_eq!(
+        data["SESSION_PARAMETERS"]["CUSTOM_PARAMETER"],
+        "set-by-authenticator"
+    );
+    assert_eq!(
+        data["SESSION_PARAMETERS"]["CLIENT_VALIDATE_DEFAULT_PARAMETERS"],
+        true
+    );
+    assert_eq!(logins[1].header("X-Custom-Auth"), Some("TEST_USER"));
+}
+
+#[tokio::test]
+async fn test_custom_authenticator_errors() {
+    let snowflake = MockServer::start(|_| login_failure("390100", "unreachable"));
+
+    let opts = test_opts_builder(
+        &snowflake.url(),
+        AuthStrategy::Custom(Arc::new(FailingAuthenticator)),
+    )
+    .build()
+    .unwrap();
+
+    let err = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .err()
+        .expect("login should fail when the authenticator does");
+
+    assert!(err.to_string().contains("vault is sealed"));
+    assert!(snowflake.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_password_authenticator() {
+    let snowflake = MockServer::start(|_| login_success());
+
+    let opts = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .build()
+        .unwrap();
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in with a password");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins[0].method, "POST");
+    let data = &logins[0].json()["data"];
+    assert_eq!(data["PASSWORD"], "hunter2");
+    assert!(data.get("AUTHENTICATOR").is_none());
+}
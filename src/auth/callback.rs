@@ -0,0 +1,214 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use crate::{SnowflakeError, error, http::url, this_errors};
+
+static SUCCESS_PAGE: &str = "<!DOCTYPE html><html><head><meta charset=\"UTF-8\"/>\
+<title>SSO Authentication Response for Snowflake</title></head><body>\
+Your identity was confirmed and propagated to Snowflake snowflakedb-rs. \
+You can close this window now and go back where you started from.</body></html>";
+
+/// A one-shot HTTP listener on localhost that waits for an identity provider to redirect the
+/// user's browser back to us.
+///
+/// Accepting is done on its own thread so waiting doesn't block or depend on any async runtime.
+pub(crate) struct CallbackListener {
+    listener: TcpListener,
+    port: u16,
+}
+
+impl CallbackListener {
+    /// Binds to `127.0.0.1:{port}`. Use `0` to let the OS pick a free port.
+    pub fn bind(port: u16) -> Result<Self, SnowflakeError> {
+        let listener = this_errors!(
+            "failed to bind localhost callback listener",
+            TcpListener::bind(("127.0.0.1", port))
+        );
+
+        let port = this_errors!(
+            "failed to get callback listener address",
+            listener.local_addr()
+        )
+        .port();
+
+        Ok(Self { listener, port })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Waits until a request carrying `param` (in the query string or a form body) arrives,
+    /// and returns all parameters of that request.
+    pub async fn wait_for(
+        self,
+        param: &'static str,
+        timeout: Duration,
+    ) -> Result<HashMap<String, String>, SnowflakeError> {
+        let (sender, receiver) = futures_channel::oneshot::channel();
+
+        std::thread::spawn(move || {
+            let _ = sender.send(accept_until(self.listener, param, timeout));
+        });
+
+        this_errors!(
+            "callback listener stopped without a response",
+            receiver.await
+        )
+    }
+}
+
+fn accept_until(
+    listener: TcpListener,
+    param: &'static str,
+    timeout: Duration,
+) -> Result<HashMap<String, String>, SnowflakeError> {
+    this_errors!(
+        "failed to configure callback listener",
+        listener.set_nonblocking(true)
+    );
+
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = mpsc::channel();
+
+    loop {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                // Each connection gets its own thread, so a browser preconnect that never sends
+                // anything doesn't hold up the redirect behind it
+                let sender = sender.clone();
+                std::thread::spawn(move || match handle_connection(stream, param) {
+                    Ok(Some(params)) => {
+                        let _ = sender.send(params);
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::debug!("dropped callback connection from {addr}: {e}"),
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if let Ok(params) = receiver.try_recv() {
+                    return Ok(params);
+                }
+                if Instant::now() >= deadline {
+                    return Err(error!(
+                        "timed out waiting for the identity provider to redirect back"
+                    ));
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => return Err(error!("failed to accept callback connection", e)),
+        }
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    param: &str,
+) -> Result<Option<HashMap<String, String>>, SnowflakeError> {
+    this_errors!(
+        "failed to configure callback connection",
+        stream.set_nonblocking(false)
+    );
+    this_errors!(
+        "failed to configure callback connection",
+        stream.set_read_timeout(Some(Duration::from_secs(10)))
+    );
+
+    let (request_line, body) = read_request(&mut stream)?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+
+    // Browsers send a CORS preflight before posting the token back to us
+    if method == "OPTIONS" {
+        write_response(
+            &mut stream,
+            "200 OK",
+            "Access-Control-Allow-Origin: *\r\n\
+             Access-Control-Allow-Methods: POST, GET\r\n\
+             Access-Control-Allow-Headers: *\r\n",
+            "",
+        );
+        return Ok(None);
+    }
+
+    let mut params = HashMap::new();
+    let query = target.split_once('?').map(|(_, q)| q).unwrap_or_default();
+    for raw in [query, body.as_str()] {
+        let pairs = this_errors!(
+            "failed to decode callback parameters",
+            url::parse_query(raw)
+        );
+        params.extend(pairs);
+    }
+
+    if params.contains_key(param) {
+        write_response(
+            &mut stream,
+            "200 OK",
+            "Content-Type: text/html\r\n",
+            SUCCESS_PAGE,
+        );
+        Ok(Some(params))
+    } else {
+        // Probably a favicon or some other request the browser made on its own
+        write_response(&mut stream, "404 Not Found", "", "");
+        Ok(None)
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<(String, String), SnowflakeError> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let read = this_errors!("failed to read callback request", stream.read(&mut chunk));
+        if read == 0 {
+            break buffer.len();
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buffer.len() < header_end + content_length {
+        let read = this_errors!("failed to read callback request", stream.read(&mut chunk));
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+
+    let body_end = buffer.len().min(header_end + content_length);
+    let body = String::from_utf8_lossy(&buffer[header_end..body_end]).to_string();
+    let request_line = head.lines().next().unwrap_or_default().to_string();
+
+    Ok((request_line, body))
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, headers: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    // The browser going away early isn't our problem, we already have what we need
+    let _ = stream.write_all(response.as_bytes());
+    let _ = stream.flush();
+}
@@ -0,0 +1,196 @@
+use std::{fmt::Debug, sync::Arc};
+
+use futures_util::{FutureExt, future::BoxFuture};
+
+use crate::{SnowflakeError, auth::Secret, http::ErrorResponse, this_errors};
+
+#[cfg(test)]
+#[path = "./login_error_test.rs"]
+mod login_error_test;
+
+// Snowflake's `nextAction` values that ask the client for more input
+static NEXT_ACTION_PASSWORD_CHANGE: &str = "PWD_CHANGE";
+static NEXT_ACTION_MFA: [&str; 2] = ["EXT_AUTHN_DUO_ALL", "EXT_AUTHN_DUO_PUSH_N_PASSCODE"];
+
+// Snowflake's login error codes, and what each means for the caller
+static LOGIN_ERROR_CODES: [(&str, LoginErrorKind); 5] = [
+    // Incorrect username or password was specified.
+    ("390100", LoginErrorKind::InvalidCredentials),
+    // User temporarily locked.
+    ("390102", LoginErrorKind::AccountLocked),
+    // JWT token is invalid.
+    ("390144", LoginErrorKind::InvalidCredentials),
+    // Invalid OAuth access token.
+    ("390303", LoginErrorKind::InvalidCredentials),
+    // OAuth access token expired.
+    ("390318", LoginErrorKind::InvalidCredentials),
+];
+
+/// Why Snowflake rejected a login.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoginErrorKind {
+    /// The password has expired or must be changed on first login.
+    PasswordChangeRequired,
+    /// Snowflake wants a second factor, e.g. a Duo push or passcode.
+    MfaRequired,
+    /// Too many failed attempts, the user is locked out for a while.
+    AccountLocked,
+    InvalidCredentials,
+    UserDisabled,
+    Other,
+}
+
+/// A failed login, as Snowflake reported it. Available from `SnowflakeError::login_error`.
+#[derive(Clone, Debug)]
+pub struct LoginError {
+    pub kind: LoginErrorKind,
+    pub code: Option<String>,
+    pub message: Option<String>,
+    /// What Snowflake expects the client to do next, e.g. `PWD_CHANGE` or `EXT_AUTHN_DUO_ALL`.
+    pub next_action: Option<String>,
+    pub authn_method: Option<String>,
+}
+
+impl LoginError {
+    pub(crate) fn from_response(err: &ErrorResponse) -> Self {
+        let code = err.code.clone().or(err.data.error_code.clone());
+
+        Self {
+            kind: classify(
+                code.as_deref(),
+                err.data.next_action.as_deref(),
+                err.message.as_deref(),
+            ),
+            code,
+            message: err.message.clone(),
+            next_action: err.data.next_action.clone(),
+            authn_method: err.data.authn_method.clone(),
+        }
+    }
+}
+
+fn classify(
+    code: Option<&str>,
+    next_action: Option<&str>,
+    message: Option<&str>,
+) -> LoginErrorKind {
+    match next_action {
+        Some(action) if action == NEXT_ACTION_PASSWORD_CHANGE => {
+            return LoginErrorKind::PasswordChangeRequired;
+        }
+        Some(action) if NEXT_ACTION_MFA.contains(&action) => return LoginErrorKind::MfaRequired,
+        _ => {}
+    }
+
+    if let Some((_, kind)) = LOGIN_ERROR_CODES
+        .iter()
+        .find(|(known, _)| code == Some(*known))
+    {
+        return *kind;
+    }
+
+    // Only for codes not listed above, the message is the last resort
+    let message = message.unwrap_or_default().to_lowercase();
+    if message.contains("locked") {
+        LoginErrorKind::AccountLocked
+    } else if message.contains("disabled") {
+        LoginErrorKind::UserDisabled
+    } else if message.contains("password")
+        && (message.contains("change") || message.contains("expired"))
+    {
+        LoginErrorKind::PasswordChangeRequired
+    } else if message.contains("multi-factor") || message.contains("mfa") {
+        LoginErrorKind::MfaRequired
+    } else if message.contains("incorrect username or password") {
+        LoginErrorKind::InvalidCredentials
+    } else {
+        LoginErrorKind::Other
+    }
+}
+
+pub type LoginPromptError = Box<dyn std::error::Error + Send + Sync>;
+
+type PromptFn =
+    dyn Fn(LoginError) -> BoxFuture<'static, Result<String, LoginPromptError>> + Send + Sync;
+
+/// Answers Snowflake when a login needs more input from the user, instead of failing it.
+///
+/// Set it with `SnowflakeConnectionOptsBuilder::login_prompt`. Without a callback for a case the
+/// login fails with the matching `LoginErrorKind`.
+#[derive(Clone, Default)]
+pub struct LoginPrompt {
+    new_password: Option<Arc<PromptFn>>,
+    mfa_passcode: Option<Arc<PromptFn>>,
+}
+
+impl LoginPrompt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called when the password must be changed, returns the new password to set.
+    ///
+    /// Later logins of the pool still use the password the strategy was created with, so update
+    /// wherever that comes from too.
+    pub fn new_password<F, Fut>(mut self, prompt: F) -> Self
+    where
+        F: Fn(LoginError) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, LoginPromptError>> + Send + 'static,
+    {
+        self.new_password = Some(Arc::new(move |err| prompt(err).boxed()));
+        self
+    }
+
+    /// Called when Snowflake asks for a second factor, returns the passcode to answer with.
+    pub fn mfa_passcode<F, Fut>(mut self, prompt: F) -> Self
+    where
+        F: Fn(LoginError) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, LoginPromptError>> + Send + 'static,
+    {
+        self.mfa_passcode = Some(Arc::new(move |err| prompt(err).boxed()));
+        self
+    }
+
+    /// Asks the matching callback for input and adds it to the login `body`, so it can be sent
+    /// again. Returns `false` if there is nothing to answer `err` with.
+    pub(crate) async fn answer(
+        &self,
+        err: &ErrorResponse,
+        body: &mut serde_json::Value,
+    ) -> Result<bool, SnowflakeError> {
+        let login_error = LoginError::from_response(err);
+
+        let (prompt, field) = match login_error.kind {
+            LoginErrorKind::PasswordChangeRequired => (&self.new_password, "CHOSEN_NEW_PASSWORD"),
+            LoginErrorKind::MfaRequired => (&self.mfa_passcode, "PASSCODE"),
+            _ => return Ok(false),
+        };
+
+        // Snowflake needs the context of the login being continued
+        let (Some(prompt), Some(ctx)) = (prompt, err.data.in_flight_ctx.as_ref()) else {
+            return Ok(false);
+        };
+
+        let answer = Secret::new(this_errors!(
+            "login prompt failed",
+            prompt(login_error.clone()).await
+        ));
+
+        body["inFlightCtx"] = ctx.clone().into();
+        body["data"][field] = answer.expose().into();
+        if login_error.kind == LoginErrorKind::MfaRequired {
+            body["data"]["EXT_AUTHN_DUO_METHOD"] = "passcode".into();
+        }
+
+        Ok(true)
+    }
+}
+
+impl Debug for LoginPrompt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginPrompt")
+            .field("new_password", &self.new_password.is_some())
+            .field("mfa_passcode", &self.mfa_passcode.is_some())
+            .finish()
+    }
+}
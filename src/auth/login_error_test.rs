@@ -0,0 +1,222 @@
+use serde_json::json;
+
+#[cfg(test)]
+use super::*;
+use crate::{
+    auth::{AuthStrategy, PasswordMfa},
+    test_utils::{
+        MockResponse, MockServer, TestClient, login_failure, login_success, test_opts_builder,
+    },
+};
+
+fn login_challenge(next_action: &str, message: &str) -> MockResponse {
+    MockResponse::json(json!({
+        "data": {
+            "authnMethod": "PASSWORD",
+            "nextAction": next_action,
+            "inFlightCtx": "in-flight-ctx"
+        },
+        "code": null,
+        "message": message,
+        "success": false
+    }))
+}
+
+#[tokio::test]
+async fn test_invalid_credentials() {
+    let snowflake = MockServer::start(|_| {
+        login_failure("390100", "Incorrect username or password was specified.")
+    });
+
+    let opts = test_opts_builder(&snowflake.url(), AuthStrategy::Password("wrong".into()))
+        .build()
+        .unwrap();
+
+    let err = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .err()
+        .expect("login should fail with the wrong password");
+
+    let login_error = err.login_error().expect("missing login error");
+    assert_eq!(login_error.kind, LoginErrorKind::InvalidCredentials);
+    assert_eq!(login_error.code.as_deref(), Some("390100"));
+}
+
+#[tokio::test]
+async fn test_password_change_without_prompt() {
+    let snowflake =
+        MockServer::start(|_| login_challenge("PWD_CHANGE", "Password change required."));
+
+    let opts = test_opts_builder(&snowflake.url(), AuthStrategy::Password("expired".into()))
+        .build()
+        .unwrap();
+
+    let err = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .err()
+        .expect("login should fail without a prompt for the new password");
+
+    assert_eq!(
+        err.login_error().map(|e| e.kind),
+        Some(LoginErrorKind::PasswordChangeRequired)
+    );
+    assert_eq!(snowflake.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_password_change_with_prompt() {
+    let snowflake = MockServer::start(|req| {
+        let body = req.json();
+        if body["data"]["CHOSEN_NEW_PASSWORD"] == "new-password" {
+            login_success()
+        } else {
+            login_challenge("PWD_CHANGE", "Password change required.")
+        }
+    });
+
+    let prompt = LoginPrompt::new().new_password(|err| async move {
+        assert_eq!(err.kind, LoginErrorKind::PasswordChangeRequired);
+        Ok("new-password".to_string())
+    });
+
+    let opts = test_opts_builder(&snowflake.url(), AuthStrategy::Password("expired".into()))
+        .login_prompt(prompt)
+        .build()
+        .unwrap();
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in after changing the password");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 2);
+
+    let body = logins[1].json();
+    assert_eq!(body["inFlightCtx"], "in-flight-ctx");
+    assert_eq!(body["data"]["PASSWORD"], "expired");
+}
+
+#[tokio::test]
+async fn test_mfa_passcode_prompt() {
+    let snowflake = MockServer::start(|req| {
+        let body = req.json();
+        if body["data"]["PASSCODE"] == "123456" {
+            login_success()
+        } else {
+            login_challenge("EXT_AUTHN_DUO_ALL", "MFA required.")
+        }
+    });
+
+    let prompt = LoginPrompt::new().mfa_passcode(|_| async { Ok("123456".to_string()) });
+
+    let opts = test_opts_builder(
+        &snowflake.url(),
+        AuthStrategy::PasswordMfa(PasswordMfa::new("hunter2")),
+    )
+    .login_prompt(prompt)
+    .build()
+    .unwrap();
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in with the prompted passcode");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 2);
+    assert_eq!(logins[1].json()["data"]["EXT_AUTHN_DUO_METHOD"], "passcode");
+}
+
+fn classify_response(body: serde_json::Value) -> LoginErrorKind {
+    let err: ErrorResponse = serde_json::from_value(body).unwrap();
+    LoginError::from_response(&err).kind
+}
+
+#[test]
+fn test_classify_by_code() {
+    // Bodies as Snowflake sends them, nextAction RETRY_LOGIN says nothing about the cause
+    let rejected = |code: &str, authn_method: &str, message: &str| {
+        json!({
+            "data": {
+                "nextAction": "RETRY_LOGIN",
+                "authnMethod": authn_method,
+                "requestId": "0b6f3c3e-5a29-4b5e-9a3f-0c2f6c1d9e51",
+                "signInOptions": {}
+            },
+            "code": code,
+            "message": message,
+            "success": false,
+            "headers": null
+        })
+    };
+
+    assert_eq!(
+        classify_response(rejected(
+            "390100",
+            "PASSWORD",
+            "Incorrect username or password was specified."
+        )),
+        LoginErrorKind::InvalidCredentials
+    );
+    assert_eq!(
+        classify_response(rejected(
+            "390102",
+            "PASSWORD",
+            "User temporarily locked. Contact your local system administrator or please try again later."
+        )),
+        LoginErrorKind::AccountLocked
+    );
+    assert_eq!(
+        classify_response(rejected(
+            "390144",
+            "KEY_PAIR",
+            "JWT token is invalid. [0b6f3c3e-5a29-4b5e-9a3f-0c2f6c1d9e51]"
+        )),
+        LoginErrorKind::InvalidCredentials
+    );
+    assert_eq!(
+        classify_response(rejected(
+            "390303",
+            "OAUTH",
+            "Invalid OAuth access token. [0b6f3c3e-5a29-4b5e-9a3f-0c2f6c1d9e51]"
+        )),
+        LoginErrorKind::InvalidCredentials
+    );
+    assert_eq!(
+        classify_response(rejected(
+            "390318",
+            "OAUTH",
+            "OAuth access token expired. [0b6f3c3e-5a29-4b5e-9a3f-0c2f6c1d9e51]"
+        )),
+        LoginErrorKind::InvalidCredentials
+    );
+}
+
+#[test]
+fn test_code_wins_over_message() {
+    // The message mentions a password, but the code says what happened
+    assert_eq!(
+        classify(
+            Some("390102"),
+            None,
+            Some("User temporarily locked after too many incorrect password attempts.")
+        ),
+        LoginErrorKind::AccountLocked
+    );
+}
+
+#[test]
+fn test_classify_by_message() {
+    // Codes without a known meaning fall back to the message
+    assert_eq!(
+        classify(Some("390199"), None, Some("User is disabled.")),
+        LoginErrorKind::UserDisabled
+    );
+    assert_eq!(
+        classify(None, None, Some("Something else went wrong.")),
+        LoginErrorKind::Other
+    );
+}
@@ -1,4 +1,26 @@
+mod authenticator;
+pub(crate) mod callback;
+mod login_error;
+mod secret;
 pub mod session;
 mod strategies;
+mod token_cache;
 
-pub use strategies::AuthStrategy;
\ No newline at end of file
+pub use authenticator::{
+    AuthResponse, Authenticator, AuthenticatorError, LoginAction, LoginRequest,
+};
+pub use login_error::{LoginError, LoginErrorKind, LoginPrompt, LoginPromptError};
+pub use secret::Secret;
+#[cfg(feature = "auth-oauth")]
+pub use strategies::OAuthClient;
+pub use strategies::{
+    AuthStrategy, BrowserOpenError, ExternalBrowser, OAuthTokenError, OAuthTokenProvider, Okta,
+    PasswordMfa, ProgrammaticAccessToken,
+};
+#[cfg(feature = "auth-wif")]
+pub use strategies::{AwsCredentials, WorkloadIdentity, WorkloadIdentityProvider};
+#[cfg(feature = "auth-cert")]
+pub use strategies::{KeyPair, KeySlot, PrivateKey};
+#[cfg(feature = "token-cache-file")]
+pub use token_cache::FileTokenCache;
+pub use token_cache::{InMemoryTokenCache, TokenCache, TokenCacheKey, TokenKind};
@@ -0,0 +1,60 @@
+use std::fmt::{Debug, Display};
+
+use zeroize::Zeroize;
+
+#[cfg(test)]
+#[path = "./secret_test.rs"]
+mod secret_test;
+
+static REDACTED: &str = "[REDACTED]";
+
+/// A credential that is never printed and is wiped from memory when dropped.
+///
+/// `Debug` and `Display` output `[REDACTED]`, and it isn't `Serialize` so it can't be written out
+/// by accident. Use [`Secret::expose`] to get the actual value.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_string())
+    }
+}
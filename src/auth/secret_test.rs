@@ -0,0 +1,40 @@
+#[cfg(test)]
+use super::*;
+
+#[cfg(test)]
+use crate::{
+    auth::{AuthStrategy, PasswordMfa, ProgrammaticAccessToken},
+    test_utils::test_opts,
+};
+
+#[test]
+fn secret_is_redacted() {
+    let secret = Secret::new("hunter2");
+
+    assert_eq!(secret.expose(), "hunter2");
+    assert_eq!(format!("{secret}"), "[REDACTED]");
+    assert_eq!(format!("{secret:?}"), "[REDACTED]");
+}
+
+#[test]
+fn secret_is_never_serialized() {
+    // Rather than a placeholder that would round-trip as the password
+    let strategy = AuthStrategy::Password("hunter2".into());
+    assert!(serde_json::to_string(&strategy).is_err());
+}
+
+#[test]
+fn connection_opts_debug_hides_credentials() {
+    let strategies = [
+        AuthStrategy::Password("hunter2".into()),
+        AuthStrategy::PasswordMfa(PasswordMfa::new("hunter2").passcode("123456")),
+        AuthStrategy::ProgrammaticAccessToken(ProgrammaticAccessToken::new("hunter2")),
+    ];
+
+    for strategy in strategies {
+        let debug = format!("{:?}", test_opts("http://127.0.0.1:1", strategy));
+
+        assert!(!debug.contains("hunter2"), "{debug}");
+        assert!(!debug.contains("123456"), "{debug}");
+    }
+}
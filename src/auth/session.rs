@@ -6,7 +6,9 @@ use std::{
 
 use crate::{
     SnowflakeError,
+    auth::{AuthResponse, LoginAction, LoginError, LoginRequest, Secret, TokenKind},
     connection::Connection,
+    driver::base::response::RawQueryResponse,
     error,
     http::{
         self, client::SnowflakeHttpClient, close::CloseSessionResponse, login::LoginResponse,
@@ -18,9 +20,24 @@ use crate::{
 static LOGIN_REQUEST_PATH: &'static str = "/session/v1/login-request";
 static TOKEN_REQUEST_PATH: &'static str = "/session/token-request";
 static CLOSE_SESSION_PATH: &'static str = "/session";
+static HEARTBEAT_PATH: &str = "/session/heartbeat";
+
+// Snowflake rejected the session token, it can be renewed with the master token
+static SESSION_EXPIRED_CODE: &str = "390112";
+// The master token expired or the session no longer exists, only a new login helps
+static SESSION_GONE_CODES: [&str; 2] = ["390111", "390114"];
+
+// Stops an authenticator that keeps asking to retry from looping forever
+static MAX_LOGIN_ATTEMPTS: u32 = 3;
+
+// Snowflake tailors response types to the client, these are the responses the driver is built around.
+// The result format itself is negotiated explicitly on every query, see `ResultFormat`.
+pub(crate) static DEFAULT_CLIENT_APP_ID: &str = "Go";
+pub(crate) static DEFAULT_CLIENT_APP_VERSION: &str = "1.18.1";
+pub(crate) static DEFAULT_APPLICATION: &str = "snowflakedb-rs";
 
 pub(crate) struct Token {
-    pub token: String,
+    pub token: Secret,
     pub validity: Duration,
     pub issued_at: Instant,
 }
@@ -30,7 +47,7 @@ impl Token {
         let validity = Duration::from_secs(u64::try_from(validity_in_seconds).unwrap_or(u64::MAX));
 
         Self {
-            token: token_string,
+            token: Secret::new(token_string),
             validity,
             issued_at: Instant::now(),
         }
@@ -41,9 +58,74 @@ impl Token {
     }
 }
 
+/// What Snowflake reported about a session when it logged in, kept up to date as queries change it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionInfo {
+    /// Changes when the session has to log in again, e.g. after its master token expired.
+    pub session_id: i64,
+    pub server_version: String,
+    pub display_user_name: String,
+
+    // `None` until a default is set or a `USE` statement picks one
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    pub warehouse: Option<String>,
+    pub role: Option<String>,
+
+    /// The session parameters Snowflake sent, by name, e.g. `TIMEZONE`.
+    pub parameters: HashMap<String, serde_json::Value>,
+}
+
+impl SessionInfo {
+    fn from_login(data: &http::login::AuthData) -> Self {
+        Self {
+            session_id: data.session_id,
+            server_version: data.server_version.clone(),
+            display_user_name: data.display_user_name.clone(),
+            database: data.session_info.database_name.clone(),
+            schema: data.session_info.schema_name.clone(),
+            warehouse: data.session_info.warehouse_name.clone(),
+            role: data.session_info.role_name.clone(),
+            parameters: data
+                .parameters
+                .iter()
+                .map(|param| (param.name.clone(), param.value.clone()))
+                .collect(),
+        }
+    }
+
+    /// Applies the context and parameters a query response reports the session was left with.
+    pub(crate) fn update(&mut self, resp: &RawQueryResponse) {
+        // Older responses leave these out, only a value that's present means anything changed
+        if let Some(database) = &resp.final_database_name {
+            self.database = Some(database.clone()).filter(|x| !x.is_empty());
+        }
+        if let Some(schema) = &resp.final_schema_name {
+            self.schema = Some(schema.clone()).filter(|x| !x.is_empty());
+        }
+        if let Some(warehouse) = &resp.final_warehouse_name {
+            self.warehouse = Some(warehouse.clone()).filter(|x| !x.is_empty());
+        }
+        if let Some(role) = &resp.final_role_name {
+            self.role = Some(role.clone()).filter(|x| !x.is_empty());
+        }
+
+        for param in &resp.parameters {
+            self.parameters
+                .insert(param.name.clone(), param.value.clone());
+        }
+    }
+}
+
 pub struct Session<C: SnowflakeHttpClient + Clone> {
     pub(crate) sequence_counter: u64,
     pub(crate) is_dirty: bool,
+    /// Whether a `BEGIN` ran without a `COMMIT` or `ROLLBACK` since. The transaction only exists
+    /// in this server session, so the session mustn't be replaced by logging in again.
+    pub(crate) in_transaction: bool,
+    pub(crate) info: SessionInfo,
+    /// How often Snowflake suggests sending a heartbeat, from the login response.
+    pub(crate) health_check_interval: Option<Duration>,
     token: Token,
     master_token: Token,
     conn: Connection<C>,
@@ -51,7 +133,12 @@ pub struct Session<C: SnowflakeHttpClient + Clone> {
 
 impl<C: SnowflakeHttpClient + Clone> Session<C> {
     pub(crate) async fn new(conn: Connection<C>) -> Result<Self, SnowflakeError> {
-        let resp = Self::login(conn.clone()).await?;
+        let resp = Self::login(conn.clone(), None).await?;
+        let info = SessionInfo::from_login(&resp);
+        let health_check_interval = u64::try_from(resp.health_check_interval)
+            .ok()
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs);
 
         let token = resp
             .token
@@ -67,6 +154,9 @@ impl<C: SnowflakeHttpClient + Clone> Session<C> {
 
         Ok(Session {
             is_dirty: false,
+            in_transaction: false,
+            info,
+            health_check_interval,
             token: Token::new(token, validity),
             master_token: Token::new(master_token, resp.master_validity),
             sequence_counter: 0,
@@ -83,35 +173,87 @@ impl<C: SnowflakeHttpClient + Clone> Session<C> {
     pub(crate) async fn get_token(&mut self) -> Result<&str, SnowflakeError> {
         if self.token.is_expired() {
             if self.master_token.is_expired() {
-                let resp = Self::login(self.conn.clone()).await?;
-
-                let token = resp
-                    .token
-                    .ok_or(error!("missing token in login response"))?;
-
-                let master_token = resp
-                    .master_token
-                    .ok_or(error!("missing master_token in login response"))?;
-
-                let validity = resp
-                    .validity
-                    .ok_or(error!("missing validity in login response"))?;
-
-                self.master_token = Token::new(master_token, resp.master_validity);
-                self.token = Token::new(token, validity);
+                self.relogin().await?;
             } else {
                 self.renew_token().await?;
             }
         }
 
-        Ok(self.token.token.as_str())
+        Ok(self.token.token.expose())
+    }
+
+    /// Recovers from Snowflake rejecting the session token with `code`, even though it hadn't
+    /// expired locally (e.g. after the machine slept or its clock drifted).
+    /// Returns `false` if `code` isn't a session expiry the request can be retried after.
+    pub(crate) async fn recover_expired(
+        &mut self,
+        code: Option<&str>,
+    ) -> Result<bool, SnowflakeError> {
+        match code {
+            Some(code) if code == SESSION_EXPIRED_CODE => {
+                // The master token may have been rejected too, in which case only a login helps
+                if self.renew_token().await.is_err() {
+                    self.relogin().await?;
+                }
+                Ok(true)
+            }
+            Some(code) if SESSION_GONE_CODES.contains(&code) => {
+                self.relogin().await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Replaces the server session with a new login, in the database, schema, warehouse and role
+    /// this one was using. Fails instead if a transaction is open, since it would be lost and the
+    /// statements after it would run outside of it.
+    async fn relogin(&mut self) -> Result<(), SnowflakeError> {
+        if self.in_transaction {
+            return Err(error!(
+                "the session expired while a transaction was open, snowflake rolled it back"
+            ));
+        }
+
+        let resp = Self::login(self.conn.clone(), Some(&self.info)).await?;
+        let info = SessionInfo::from_login(&resp);
+
+        let token = resp
+            .token
+            .ok_or(error!("missing token in login response"))?;
+
+        let master_token = resp
+            .master_token
+            .ok_or(error!("missing master_token in login response"))?;
+
+        let validity = resp
+            .validity
+            .ok_or(error!("missing validity in login response"))?;
+
+        self.master_token = Token::new(master_token, resp.master_validity);
+        self.token = Token::new(token, validity);
+        self.info = info;
+
+        Ok(())
     }
 
     pub(crate) fn get_conn(&self) -> Connection<C> {
         self.conn.clone()
     }
 
-    async fn login(conn: Connection<C>) -> Result<http::login::AuthData, SnowflakeError> {
+    /// Logs in, restoring the context of `previous` if this replaces an expired session.
+    async fn login(
+        conn: Connection<C>,
+        previous: Option<&SessionInfo>,
+    ) -> Result<http::login::AuthData, SnowflakeError> {
+        Self::login_attempt(conn, previous, 1).await
+    }
+
+    async fn login_attempt(
+        conn: Connection<C>,
+        previous: Option<&SessionInfo>,
+        attempt: u32,
+    ) -> Result<http::login::AuthData, SnowflakeError> {
         let opts_clone = conn.get_opts();
         // let app_version = env!("CARGO_PKG_VERSION");
         let current_os = std::env::consts::OS;
@@ -119,19 +261,18 @@ impl<C: SnowflakeHttpClient + Clone> Session<C> {
         // TODO: Maybe actually retrieve release info
         let os_version = format!("{current_os}-{current_os_arch}");
 
-        let mut login_body = json!({
+        let login_body = json!({
             "data": {
-                // This *needs* to be Go. Snowflake API changes response types depending on the library used
-                "CLIENT_APP_ID": "Go",
-                "CLIENT_APP_VERSION": "1.18.1",
+                "CLIENT_APP_ID": opts_clone.client_app_id.as_str(),
+                "CLIENT_APP_VERSION": opts_clone.client_app_version.as_str(),
                 "SVN_REVISION": "",
-                "ACCOUNT_NAME": opts_clone.account_id.as_str(),
+                "ACCOUNT_NAME": conn.account().name(),
                 "LOGIN_NAME": opts_clone.username.as_str(),
                 "SESSION_PARAMETERS": {
                     "CLIENT_VALIDATE_DEFAULT_PARAMETERS": true,
                 },
                 "CLIENT_ENVIRONMENT": {
-                    "APPLICATION": "snowflakedb-rs",
+                    "APPLICATION": opts_clone.application.as_str(),
                     "OS": current_os,
                     "OS_VERSION": os_version,
                     "OCSP_MODE": "FAIL_OPEN",
@@ -140,65 +281,244 @@ impl<C: SnowflakeHttpClient + Clone> Session<C> {
             }
         });
 
+        let mut headers = HashMap::new();
+        headers.insert("ACCEPT".to_string(), "application/json".to_string());
+
+        let mut request = LoginRequest::new(
+            conn.account().name(),
+            &opts_clone.username,
+            login_body,
+            headers,
+        );
+
+        // Set on every login, so sessions that log in again keep them
+        for (key, value) in &opts_clone.session_parameters {
+            request.set_session_parameter(key.as_str(), value.clone());
+        }
+
+        // Interactive and MFA logins can skip the prompt with a token from an earlier login
+        let cached_kind = match &opts_clone.strategy {
+            super::AuthStrategy::PasswordMfa(_) => Some(TokenKind::MfaToken),
+            super::AuthStrategy::ExternalBrowser(_) => Some(TokenKind::IdToken),
+            _ => None,
+        };
+        let cached_token = cached_kind.and_then(|kind| conn.get_cached_token(kind));
+
+        let authenticator = opts_clone.strategy.authenticator();
+
+        if let Some(authenticator) = authenticator.as_ref() {
+            authenticator
+                .authenticate(&mut request)
+                .await
+                .map_err(|e| match e.downcast::<SnowflakeError>() {
+                    Ok(e) => *e,
+                    Err(e) => error!("authenticator failed to prepare the login request", e),
+                })?;
+        }
+
         match &opts_clone.strategy {
-            super::AuthStrategy::Password(password) => {
-                let obj_map = login_body["data"]
-                    .as_object_mut()
-                    .expect("unexpected none object in login_body");
-                obj_map.insert("PASSWORD".to_string(), password.as_str().into());
+            super::AuthStrategy::ExternalBrowser(browser) => {
+                use crate::auth::strategies;
+
+                if let Some(id_token) = cached_token.as_ref().map(Secret::expose) {
+                    request.set("AUTHENTICATOR", "ID_TOKEN");
+                    request.set("TOKEN", id_token);
+                } else {
+                    let login = strategies::external_browser::authenticate(&conn, browser).await?;
+
+                    request.set("AUTHENTICATOR", "EXTERNALBROWSER");
+                    request.set("TOKEN", login.token);
+                    request.set("PROOF_KEY", login.proof_key);
+                }
+
+                request.set_session_parameter("CLIENT_STORE_TEMPORARY_CREDENTIAL", true);
             }
-            #[cfg(feature = "auth-cert")]
-            super::AuthStrategy::Certificate(cert) => {
+            super::AuthStrategy::Okta(okta) => {
                 use crate::auth::strategies;
 
-                let obj_map = login_body["data"]
-                    .as_object_mut()
-                    .expect("unexpected none object in login_body");
-                obj_map.insert("AUTHENTICATOR".to_string(), "SNOWFLAKE_JWT".into());
+                let saml_response = strategies::okta::authenticate(&conn, okta).await?;
 
-                let token = strategies::cert_auth::create_cert_auth_jwt(
-                    &opts_clone.account_id,
-                    &opts_clone.username,
-                    &cert,
-                )?;
+                request.set("AUTHENTICATOR", okta.url());
+                request.set("RAW_SAML_RESPONSE", saml_response);
+            }
+            #[cfg(feature = "auth-oauth")]
+            super::AuthStrategy::OAuthClient(client) => {
+                let token = client.access_token(&conn).await?;
 
-                obj_map.insert("TOKEN".to_string(), token.into());
+                request.set("AUTHENTICATOR", "OAUTH");
+                request.set("TOKEN", token);
             }
+            #[cfg(feature = "auth-wif")]
+            super::AuthStrategy::WorkloadIdentity(wif) => {
+                use crate::auth::strategies;
+
+                let attestation = strategies::wif::attest(&conn, wif).await?;
+
+                request.set("AUTHENTICATOR", "WORKLOAD_IDENTITY");
+                request.set("PROVIDER", wif.provider().as_str());
+                request.set("TOKEN", attestation);
+            }
+            super::AuthStrategy::PasswordMfa(mfa) => {
+                request.set("AUTHENTICATOR", "USERNAME_PASSWORD_MFA");
+                request.set("PASSWORD", mfa.password.expose());
+
+                // A cached MFA token from an earlier login skips the MFA prompt entirely
+                if let Some(token) = cached_token.as_ref().map(Secret::expose) {
+                    request.set("TOKEN", token);
+                } else if mfa.passcode_in_password {
+                    request.set("EXT_AUTHN_DUO_METHOD", "passcode");
+                } else if let Some(passcode) = mfa.passcode.as_ref().map(Secret::expose) {
+                    request.set("PASSCODE", passcode);
+                    request.set("EXT_AUTHN_DUO_METHOD", "passcode");
+                } else {
+                    request.set("EXT_AUTHN_DUO_METHOD", "push");
+                }
+
+                request.set_session_parameter("CLIENT_REQUEST_MFA_TOKEN", true);
+            }
+            // Everything else was handled by its authenticator above
+            _ => {}
         };
 
+        let (mut login_body, headers) = request.into_parts();
+
+        // The names a session reports are exact, so they're quoted unless they'd resolve anyway
+        let context = |previous: Option<&String>, default: &Option<String>| {
+            previous
+                .map(|name| quote_identifier(name))
+                .or(default.clone())
+        };
         let params = http::params!(
-            ("databaseName", opts_clone.database),
-            ("warehouse", opts_clone.warehouse),
-            ("schemaName", opts_clone.schema),
-            ("roleName", opts_clone.role),
+            (
+                "databaseName",
+                context(
+                    previous.and_then(|p| p.database.as_ref()),
+                    &opts_clone.database
+                )
+            ),
+            (
+                "warehouse",
+                context(
+                    previous.and_then(|p| p.warehouse.as_ref()),
+                    &opts_clone.warehouse
+                )
+            ),
+            (
+                "schemaName",
+                context(previous.and_then(|p| p.schema.as_ref()), &opts_clone.schema)
+            ),
+            (
+                "roleName",
+                context(previous.and_then(|p| p.role.as_ref()), &opts_clone.role)
+            ),
         );
 
-        let mut headers = HashMap::new();
-        headers.insert("ACCEPT".to_string(), "application/json".to_string());
+        let mut prompts = 0;
+        let resp = loop {
+            let request = this_errors!(
+                "failed to build login request",
+                http::RequestBuilder::default()
+                    .connection(conn.clone())
+                    .params(params.clone())
+                    .path(LOGIN_REQUEST_PATH)
+                    .headers(headers.clone())
+                    .retry(true)
+                    .build()
+            );
+
+            let resp = request
+                .post::<http::login::LoginResponse>(login_body.clone())
+                .await?;
+
+            // Answer a password change or MFA challenge and continue the same login
+            if let (LoginResponse::Failure(err), Some(prompt)) =
+                (&resp, opts_clone.login_prompt.as_ref())
+                && prompts < MAX_LOGIN_ATTEMPTS
+                && prompt.answer(err, &mut login_body).await?
+            {
+                prompts += 1;
+                continue;
+            }
 
-        let request = this_errors!(
-            "failed to build login request",
-            http::RequestBuilder::default()
-                .connection(conn.clone())
-                .params(params)
-                .path(LOGIN_REQUEST_PATH)
-                .headers(headers)
-                .build()
-        );
+            break resp;
+        };
 
-        let resp = request
-            .post::<http::login::LoginResponse>(login_body)
-            .await?;
+        let action = authenticator.as_ref().map(|authenticator| {
+            authenticator.on_response(&match &resp {
+                LoginResponse::Success(data) => AuthResponse {
+                    success: true,
+                    code: data.code.clone(),
+                    message: data.message.clone(),
+                    next_action: None,
+                    authn_method: None,
+                },
+                LoginResponse::Failure(err) => AuthResponse {
+                    success: false,
+                    code: err.code.clone(),
+                    message: err.message.clone(),
+                    next_action: err.data.next_action.clone(),
+                    authn_method: err.data.authn_method.clone(),
+                },
+            })
+        });
 
         match resp {
-            LoginResponse::Success(data) => Ok(data.data),
-            LoginResponse::Failure(err) => Err(error!(err.message)),
+            LoginResponse::Success(data) => {
+                if let Some(token) = data.data.id_token.as_deref() {
+                    conn.set_cached_token(TokenKind::IdToken, token);
+                }
+
+                if let Some(token) = data.data.mfa_token.as_deref() {
+                    conn.set_cached_token(TokenKind::MfaToken, token);
+                }
+
+                Ok(data.data)
+            }
+            // The cached token was rejected, forget it and go through the full flow again
+            LoginResponse::Failure(_) if cached_token.is_some() => {
+                if let Some(kind) = cached_kind {
+                    conn.evict_cached_token(kind);
+                }
+                Box::pin(Self::login_attempt(conn, previous, attempt + 1)).await
+            }
+            // The access token may have been revoked or expired early, get a new one and try once more
+            #[cfg(feature = "auth-oauth")]
+            LoginResponse::Failure(_)
+                if attempt == 1
+                    && matches!(opts_clone.strategy, super::AuthStrategy::OAuthClient(_)) =>
+            {
+                if let super::AuthStrategy::OAuthClient(client) = &opts_clone.strategy {
+                    client.invalidate_access_token().await;
+                }
+                Box::pin(Self::login_attempt(conn, previous, attempt + 1)).await
+            }
+            LoginResponse::Failure(_)
+                if action == Some(LoginAction::Retry) && attempt < MAX_LOGIN_ATTEMPTS =>
+            {
+                Box::pin(Self::login_attempt(conn, previous, attempt + 1)).await
+            }
+            // Keep PAT failures distinct so they aren't mistaken for an expired master token
+            LoginResponse::Failure(err)
+                if matches!(
+                    opts_clone.strategy,
+                    super::AuthStrategy::ProgrammaticAccessToken(_)
+                ) =>
+            {
+                Err(error!(
+                    "programmatic access token was rejected by snowflake",
+                    err.message.clone()
+                )
+                .with_login_error(LoginError::from_response(&err)))
+            }
+            LoginResponse::Failure(err) => {
+                Err(error!(err.message.clone()).with_login_error(LoginError::from_response(&err)))
+            }
         }
     }
 
     async fn renew_token(&mut self) -> Result<(), SnowflakeError> {
         let body = json!({
-            "oldSessionToken": self.token.token,
+            "oldSessionToken": self.token.token.expose(),
             "requestType": "RENEW"
         });
 
@@ -208,7 +528,7 @@ impl<C: SnowflakeHttpClient + Clone> Session<C> {
                 .connection(self.conn.clone())
                 .headers([("ACCEPT".to_string(), "application/snowflake".to_string())])
                 .path(TOKEN_REQUEST_PATH)
-                .auth_token(&self.master_token.token)
+                .auth_token(self.master_token.token.expose())
                 .build()
         );
 
@@ -228,8 +548,41 @@ impl<C: SnowflakeHttpClient + Clone> Session<C> {
         }
     }
 
+    /// Tells Snowflake the session is still in use, so it doesn't expire while idle.
+    ///
+    /// Only renews the session token if needed and never logs in again, so it can run in the
+    /// background without prompting for credentials.
+    pub(crate) async fn heartbeat(&mut self) -> Result<(), SnowflakeError> {
+        if self.token.is_expired() {
+            if self.master_token.is_expired() {
+                return Err(error!("session expired, it logs in again when next used"));
+            }
+            self.renew_token().await?;
+        }
+
+        let request = this_errors!(
+            "failed to build heartbeat request",
+            http::RequestBuilder::default()
+                .connection(self.conn.clone())
+                .headers([("ACCEPT".to_string(), "application/snowflake".to_string())])
+                .path(HEARTBEAT_PATH)
+                .auth_token(self.token.token.expose())
+                .build()
+        );
+
+        let resp = request
+            .post::<http::GenericResponse<serde_json::Value>>(json!({}))
+            .await?;
+
+        match resp.code.as_deref() {
+            _ if resp.success => Ok(()),
+            Some(code) if code == SESSION_EXPIRED_CODE => self.renew_token().await,
+            _ => Err(error!("heartbeat failed", resp.message)),
+        }
+    }
+
     pub async fn close(self) -> Result<(), SnowflakeError> {
-        let token = self.master_token.token;
+        let token = self.master_token.token.clone();
 
         let request = this_errors!(
             "failed to build close session request",
@@ -254,3 +607,46 @@ impl<C: SnowflakeHttpClient + Clone> Session<C> {
         }
     }
 }
+
+/// The statements that open or end a transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TransactionStatement {
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl TransactionStatement {
+    /// Snowflake doesn't report whether a transaction is open, so the driver follows the SQL.
+    pub(crate) fn parse(sql: &str) -> Option<Self> {
+        let mut words = sql
+            .split(|c: char| c.is_whitespace() || c == ';')
+            .filter(|word| !word.is_empty())
+            .map(str::to_ascii_uppercase);
+
+        match (words.next()?.as_str(), words.next().as_deref()) {
+            // A bare `BEGIN ... END` is a Snowflake Scripting block, not a transaction
+            ("BEGIN", None | Some("TRANSACTION" | "WORK" | "NAME")) => Some(Self::Begin),
+            ("START", Some("TRANSACTION")) => Some(Self::Begin),
+            ("COMMIT", _) => Some(Self::Commit),
+            ("ROLLBACK", _) => Some(Self::Rollback),
+            _ => None,
+        }
+    }
+}
+
+/// Quotes `name` unless it's an identifier that resolves to itself unquoted.
+fn quote_identifier(name: &str) -> String {
+    let unquoted = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_uppercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_' || c == '$');
+
+    match unquoted {
+        true => name.to_string(),
+        false => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
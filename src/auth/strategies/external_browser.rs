@@ -0,0 +1,169 @@
+use std::{fmt::Debug, process::Command, sync::Arc, time::Duration};
+
+use serde_json::{Map, Value};
+
+use crate::{
+    SnowflakeError, auth::callback::CallbackListener, connection::Connection, error,
+    http::client::SnowflakeHttpClient,
+};
+
+#[cfg(test)]
+#[path = "./external_browser_test.rs"]
+mod external_browser_test;
+
+pub type BrowserOpenError = Box<dyn std::error::Error + Send + Sync>;
+
+pub(crate) type OpenerFn = dyn Fn(&str) -> Result<(), BrowserOpenError> + Send + Sync;
+
+/// Options for `AuthStrategy::ExternalBrowser`, Snowflake's browser based SSO flow.
+///
+/// The user is sent to your identity provider in their browser, which then redirects back to a
+/// listener on `localhost` with a SAML token that is used to log in.
+#[derive(Clone)]
+pub struct ExternalBrowser {
+    callback_port: u16,
+    timeout: Duration,
+    opener: Option<Arc<OpenerFn>>,
+}
+
+impl ExternalBrowser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Port the localhost callback listener binds to. Defaults to `0`, a random free port.
+    pub fn callback_port(mut self, port: u16) -> Self {
+        self.callback_port = port;
+        self
+    }
+
+    /// How long to wait for the identity provider to redirect back. Defaults to 120 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Replaces how the SSO URL is opened. By default the system browser is launched, and the
+    /// login fails with the URL in the error if that fails. Use this to show it to the user instead,
+    /// e.g. on a machine without a browser.
+    pub fn open_with<F>(mut self, opener: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), BrowserOpenError> + Send + Sync + 'static,
+    {
+        self.opener = Some(Arc::new(opener));
+        self
+    }
+
+    fn open(&self, url: &str) -> Result<(), SnowflakeError> {
+        open_url(self.opener.as_deref(), url)
+    }
+}
+
+impl Default for ExternalBrowser {
+    fn default() -> Self {
+        Self {
+            callback_port: 0,
+            timeout: Duration::from_secs(120),
+            opener: None,
+        }
+    }
+}
+
+impl Debug for ExternalBrowser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExternalBrowser")
+            .field("callback_port", &self.callback_port)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+pub(crate) struct BrowserLogin {
+    pub token: String,
+    pub proof_key: String,
+}
+
+// Taken from:
+// https://github.com/snowflakedb/gosnowflake/blob/master/authexternalbrowser.go
+pub(crate) async fn authenticate<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    browser: &ExternalBrowser,
+) -> Result<BrowserLogin, SnowflakeError> {
+    let listener = CallbackListener::bind(browser.callback_port)?;
+
+    let mut extra = Map::new();
+    extra.insert(
+        "BROWSER_MODE_REDIRECT_PORT".to_string(),
+        Value::String(listener.port().to_string()),
+    );
+
+    let data = super::authenticator_request(conn, "EXTERNALBROWSER", extra).await?;
+
+    let sso_url = data
+        .sso_url
+        .ok_or(error!("missing ssoUrl in authenticator response"))?;
+
+    let proof_key = data
+        .proof_key
+        .ok_or(error!("missing proofKey in authenticator response"))?;
+
+    browser.open(&sso_url)?;
+
+    let mut params = listener.wait_for("token", browser.timeout).await?;
+    let token = params
+        .remove("token")
+        .ok_or(error!("missing token in browser callback"))?;
+
+    Ok(BrowserLogin { token, proof_key })
+}
+
+/// Opens `url` with `opener`, or the system browser. If no browser could be launched the error
+/// has the URL, so the caller can still get it to the user.
+pub(crate) fn open_url(opener: Option<&OpenerFn>, url: &str) -> Result<(), SnowflakeError> {
+    if let Some(opener) = opener {
+        return opener(url).map_err(|e| error!("failed to open sso url", e));
+    }
+
+    open_system_browser(url).map_err(|_| {
+        error!(
+            "unable to open a browser, go to this url to log in or set open_with",
+            url
+        )
+    })
+}
+
+fn open_system_browser(url: &str) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut c = Command::new("open");
+        c.arg(url);
+        c
+    };
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut c = Command::new("rundll32");
+        c.args(["url.dll,FileProtocolHandler", url]);
+        c
+    };
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = {
+        let mut c = Command::new("xdg-open");
+        c.arg(url);
+        c
+    };
+
+    let status = command
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(
+            "browser launcher exited unsuccessfully",
+        ))
+    }
+}
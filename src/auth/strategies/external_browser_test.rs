@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+
+#[cfg(test)]
+use super::*;
+use crate::{
+    auth::{AuthStrategy, InMemoryTokenCache, TokenCache, TokenCacheKey, TokenKind},
+    test_utils::{
+        MockResponse, MockServer, TestClient, login_failure, login_success, login_success_with,
+        send_request, test_opts, test_opts_builder,
+    },
+};
+
+fn mock_snowflake(idp_url: String) -> MockServer {
+    MockServer::start(move |req| match req.path.as_str() {
+        "/session/authenticator-request" => {
+            let port = req.json()["data"]["BROWSER_MODE_REDIRECT_PORT"]
+                .as_str()
+                .unwrap()
+                .to_string();
+
+            MockResponse::json(json!({
+                "data": {
+                    "ssoUrl": format!("{idp_url}/sso?port={port}"),
+                    "proofKey": "test-proof-key"
+                },
+                "code": null,
+                "message": null,
+                "success": true
+            }))
+        }
+        "/session/v1/login-request" => {
+            let data = &req.json()["data"];
+            match data["AUTHENTICATOR"].as_str() {
+                Some("EXTERNALBROWSER") => login_success_with(json!({ "idToken": "id-token" })),
+                Some("ID_TOKEN") if data["TOKEN"] == "id-token" => login_success(),
+                _ => login_failure("390195", "The provided ID Token is invalid."),
+            }
+        }
+        _ => MockResponse::status(404),
+    })
+}
+
+/// Acts as the user's browser: signs in at the IdP, then gets redirected back with a SAML token.
+fn signing_in_browser() -> ExternalBrowser {
+    ExternalBrowser::new()
+        .timeout(Duration::from_secs(10))
+        .open_with(|sso_url| {
+            let sso_url = sso_url.to_string();
+            std::thread::spawn(move || {
+                send_request("GET", &sso_url, &HashMap::new(), &[]).unwrap();
+
+                let port = sso_url.rsplit_once("port=").unwrap().1;
+                let callback = format!("http://127.0.0.1:{port}/?token=saml%2Btoken");
+                send_request("GET", &callback, &HashMap::new(), &[]).unwrap();
+            });
+            Ok(())
+        })
+}
+
+#[tokio::test]
+async fn test_external_browser_login() {
+    let idp = MockServer::start(|_| MockResponse::html("<html>Sign in</html>"));
+    let snowflake = mock_snowflake(idp.url());
+
+    let browser = signing_in_browser();
+
+    let opts = test_opts(&snowflake.url(), AuthStrategy::ExternalBrowser(browser));
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in through the browser flow");
+
+    assert_eq!(idp.requests_to("/sso").len(), 1);
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 1);
+
+    let data = &logins[0].json()["data"];
+    assert_eq!(data["AUTHENTICATOR"], "EXTERNALBROWSER");
+    assert_eq!(data["TOKEN"], "saml+token");
+    assert_eq!(data["PROOF_KEY"], "test-proof-key");
+    assert_eq!(
+        data["SESSION_PARAMETERS"]["CLIENT_STORE_TEMPORARY_CREDENTIAL"],
+        true
+    );
+}
+
+#[tokio::test]
+async fn test_external_browser_ignores_broken_connections() {
+    let idp = MockServer::start(|_| MockResponse::html("<html>Sign in</html>"));
+    let snowflake = mock_snowflake(idp.url());
+
+    // Browsers preconnect speculatively and may never use the socket, and anything else on the
+    // machine can poke the port; neither may end the wait for the real redirect
+    let browser = ExternalBrowser::new()
+        .timeout(Duration::from_secs(5))
+        .open_with(|sso_url| {
+            let port: u16 = sso_url.rsplit_once("port=").unwrap().1.parse().unwrap();
+            std::thread::spawn(move || {
+                let _preconnect = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+
+                // Not valid UTF-8 once decoded, so reading the parameters fails
+                let mut garbage = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+                std::io::Write::write_all(&mut garbage, b"GET /?token=%ff HTTP/1.1\r\n\r\n")
+                    .unwrap();
+                drop(garbage);
+
+                let callback = format!("http://127.0.0.1:{port}/?token=saml%2Btoken");
+                send_request("GET", &callback, &HashMap::new(), &[]).unwrap();
+            });
+            Ok(())
+        });
+
+    let opts = test_opts(&snowflake.url(), AuthStrategy::ExternalBrowser(browser));
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("a broken callback connection ended the browser flow");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 1);
+    assert_eq!(logins[0].json()["data"]["TOKEN"], "saml+token");
+}
+
+#[tokio::test]
+async fn test_id_token_skips_browser() {
+    let idp = MockServer::start(|_| MockResponse::html("<html>Sign in</html>"));
+    let snowflake = mock_snowflake(idp.url());
+
+    let mut opts = test_opts(
+        &snowflake.url(),
+        AuthStrategy::ExternalBrowser(signing_in_browser()),
+    );
+    opts.pool_size = 2;
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in through the browser flow");
+
+    assert_eq!(idp.requests_to("/sso").len(), 1);
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 2);
+
+    let data = &logins[1].json()["data"];
+    assert_eq!(data["AUTHENTICATOR"], "ID_TOKEN");
+    assert_eq!(data["TOKEN"], "id-token");
+    assert!(data.get("PROOF_KEY").is_none());
+}
+
+#[tokio::test]
+async fn test_rejected_id_token_is_evicted() {
+    let idp = MockServer::start(|_| MockResponse::html("<html>Sign in</html>"));
+    let snowflake = mock_snowflake(idp.url());
+
+    let cache = InMemoryTokenCache::new();
+    let key = TokenCacheKey::new("TEST_ACCOUNT", "TEST_USER", TokenKind::IdToken);
+    cache.set(&key, "expired-id-token");
+
+    let mut builder = test_opts_builder(
+        &snowflake.url(),
+        AuthStrategy::ExternalBrowser(signing_in_browser()),
+    );
+    let opts = builder.token_cache(cache).build().unwrap();
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to fall back to the browser flow");
+
+    assert_eq!(idp.requests_to("/sso").len(), 1);
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 2);
+    assert_eq!(logins[0].json()["data"]["TOKEN"], "expired-id-token");
+    assert_eq!(logins[1].json()["data"]["AUTHENTICATOR"], "EXTERNALBROWSER");
+}
+
+#[tokio::test]
+async fn test_external_browser_times_out() {
+    let idp = MockServer::start(|_| MockResponse::html("<html>Sign in</html>"));
+    let snowflake = mock_snowflake(idp.url());
+
+    let browser = ExternalBrowser::new()
+        .timeout(Duration::from_millis(200))
+        .open_with(|_| Ok(()));
+
+    let opts = test_opts(&snowflake.url(), AuthStrategy::ExternalBrowser(browser));
+    let result = opts.connect_json_with_client::<TestClient>().await;
+
+    assert!(result.is_err());
+    assert!(
+        snowflake
+            .requests_to("/session/v1/login-request")
+            .is_empty()
+    );
+}
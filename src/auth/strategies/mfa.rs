@@ -0,0 +1,40 @@
+use crate::auth::Secret;
+
+#[cfg(test)]
+#[path = "./mfa_test.rs"]
+mod mfa_test;
+
+/// Options for `AuthStrategy::PasswordMfa`, password authentication with multi-factor authentication.
+///
+/// Without a passcode Snowflake sends a push notification (e.g. Duo Push) to the user.
+/// The MFA token Snowflake returns after the first login is reused for every other session of
+/// the pool, so a pool only triggers one push or needs one passcode at startup.
+/// The account needs `ALLOW_CLIENT_MFA_CACHING` enabled for Snowflake to return that token.
+#[derive(Clone, Debug)]
+pub struct PasswordMfa {
+    pub(crate) password: Secret,
+    pub(crate) passcode: Option<Secret>,
+    pub(crate) passcode_in_password: bool,
+}
+
+impl PasswordMfa {
+    pub fn new(password: impl Into<String>) -> Self {
+        Self {
+            password: Secret::new(password),
+            passcode: None,
+            passcode_in_password: false,
+        }
+    }
+
+    /// A one-time passcode from the user's authenticator app, used instead of a push notification.
+    pub fn passcode(mut self, passcode: impl Into<String>) -> Self {
+        self.passcode = Some(Secret::new(passcode));
+        self
+    }
+
+    /// The passcode has been appended to the end of the password.
+    pub fn passcode_in_password(mut self) -> Self {
+        self.passcode_in_password = true;
+        self
+    }
+}
@@ -0,0 +1,86 @@
+use serde_json::json;
+
+#[cfg(test)]
+use super::*;
+use crate::{
+    auth::{AuthStrategy, TokenKind, session::Session},
+    connection::Connection,
+    test_utils::{
+        MockResponse, MockServer, TestClient, login_failure, login_success_with, test_opts,
+    },
+};
+
+#[tokio::test]
+async fn test_mfa_token_reused_across_pool() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => {
+            let data = &req.json()["data"];
+            if data["TOKEN"] == "mfa-token" || data["PASSCODE"] == "123456" {
+                login_success_with(json!({ "mfaToken": "mfa-token" }))
+            } else {
+                login_failure("394507", "MFA passcode is invalid.")
+            }
+        }
+        _ => MockResponse::status(404),
+    });
+
+    let strategy = AuthStrategy::PasswordMfa(PasswordMfa::new("hunter2").passcode("123456"));
+    let mut opts = test_opts(&snowflake.url(), strategy);
+    opts.pool_size = 3;
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in with mfa");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 3);
+
+    let first = &logins[0].json()["data"];
+    assert_eq!(first["AUTHENTICATOR"], "USERNAME_PASSWORD_MFA");
+    assert_eq!(first["PASSCODE"], "123456");
+    assert_eq!(
+        first["SESSION_PARAMETERS"]["CLIENT_REQUEST_MFA_TOKEN"],
+        true
+    );
+    assert!(first.get("TOKEN").is_none());
+
+    for login in logins[1..].iter() {
+        let data = &login.json()["data"];
+        assert_eq!(data["TOKEN"], "mfa-token");
+        assert!(data.get("PASSCODE").is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_rejected_mfa_token_is_evicted() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => {
+            let data = &req.json()["data"];
+            if data["TOKEN"] == "stale-token" {
+                login_failure("390127", "MFA token is invalid.")
+            } else {
+                login_success_with(json!({ "mfaToken": "fresh-token" }))
+            }
+        }
+        _ => MockResponse::status(404),
+    });
+
+    let strategy = AuthStrategy::PasswordMfa(PasswordMfa::new("hunter2"));
+    let conn = Connection::new(TestClient, test_opts(&snowflake.url(), strategy));
+    conn.set_cached_token(TokenKind::MfaToken, "stale-token");
+
+    Session::new(conn.clone())
+        .await
+        .expect("failed to fall back to a push");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 2);
+    assert_eq!(logins[1].json()["data"]["EXT_AUTHN_DUO_METHOD"], "push");
+    assert_eq!(
+        conn.get_cached_token(TokenKind::MfaToken)
+            .as_ref()
+            .map(Secret::expose),
+        Some("fresh-token")
+    );
+}
@@ -1,15 +1,150 @@
+use std::sync::Arc;
+
 use serde::Serialize;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    SnowflakeError,
+    auth::{Authenticator, Secret},
+    connection::Connection,
+    error,
+    http::{
+        self,
+        client::SnowflakeHttpClient,
+        login::{AuthenticatorRequestData, AuthenticatorRequestResponse},
+    },
+    this_errors,
+};
 
 #[cfg(feature = "auth-cert")]
 pub(crate) mod cert_auth;
+pub(crate) mod external_browser;
+pub(crate) mod mfa;
+pub(crate) mod oauth;
+#[cfg(feature = "auth-oauth")]
+pub(crate) mod oauth_client;
+pub(crate) mod okta;
+pub(crate) mod password;
+pub(crate) mod pat;
+#[cfg(feature = "auth-wif")]
+pub(crate) mod wif;
+
+#[cfg(feature = "auth-cert")]
+pub use cert_auth::{KeyPair, KeySlot, PrivateKey};
+pub use external_browser::{BrowserOpenError, ExternalBrowser};
+pub use mfa::PasswordMfa;
+pub use oauth::{OAuthTokenError, OAuthTokenProvider};
+#[cfg(feature = "auth-oauth")]
+pub use oauth_client::OAuthClient;
+pub use okta::Okta;
+pub use pat::ProgrammaticAccessToken;
+#[cfg(feature = "auth-wif")]
+pub use wif::{AwsCredentials, WorkloadIdentity, WorkloadIdentityProvider};
+
+static AUTHENTICATOR_REQUEST_PATH: &str = "/session/authenticator-request";
 
 // Snowflake Auth Strategies can be found here:
 // https://github.com/snowflakedb/gosnowflake/blob/master/auth.go#L139
 #[derive(Serialize, Clone, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum AuthStrategy {
-    Password(String),
+    /// Authenticate with the user's password. A `String` or `&str` converts into a `Secret` with `.into()`.
+    #[serde(skip)]
+    Password(Secret),
+    /// Authenticate with an RSA key pair, optionally with a secondary key for rotation.
+    /// A `PrivateKey` or PEM string converts into a `KeyPair` with `.into()`.
     #[cfg(feature = "auth-cert")]
-    Certificate(String),
-    // TODO: Add more...
+    #[serde(skip)]
+    Certificate(KeyPair),
+    /// Authenticate with a password and multi-factor authentication (`USERNAME_PASSWORD_MFA`).
+    #[serde(skip)]
+    PasswordMfa(PasswordMfa),
+    /// Authenticate with an OAuth access token issued by an external IdP.
+    /// The provider is asked for a fresh token every time the session needs to log in again.
+    #[serde(skip)]
+    OAuth(OAuthTokenProvider),
+    /// Authenticate with OAuth access tokens the driver requests from a token endpoint itself.
+    #[cfg(feature = "auth-oauth")]
+    #[serde(skip)]
+    OAuthClient(OAuthClient),
+    /// Authenticate with a programmatic access token (PAT) generated for the user.
+    #[serde(skip)]
+    ProgrammaticAccessToken(ProgrammaticAccessToken),
+    /// Authenticate through your identity provider in a web browser (SSO).
+    #[serde(skip)]
+    ExternalBrowser(ExternalBrowser),
+    /// Authenticate through native Okta SSO without a browser.
+    #[serde(skip)]
+    Okta(Okta),
+    /// Authenticate as the cloud workload this runs on (AWS, Azure, GCP or OIDC), without a stored secret.
+    #[cfg(feature = "auth-wif")]
+    #[serde(skip)]
+    WorkloadIdentity(WorkloadIdentity),
+    /// Authenticate with your own `Authenticator`, for auth methods not supported out of the box.
+    #[serde(skip)]
+    Custom(Arc<dyn Authenticator>),
+}
+
+impl AuthStrategy {
+    /// The `Authenticator` for strategies that only need to add credentials to the login request.
+    /// Strategies with their own handshake (SSO, MFA) are handled by `Session::login` directly.
+    pub(crate) fn authenticator(&self) -> Option<Box<dyn Authenticator + '_>> {
+        match self {
+            AuthStrategy::Password(password) => {
+                Some(Box::new(password::PasswordAuthenticator(password.expose())))
+            }
+            #[cfg(feature = "auth-cert")]
+            AuthStrategy::Certificate(key) => Some(Box::new(key)),
+            AuthStrategy::OAuth(provider) => Some(Box::new(provider)),
+            AuthStrategy::ProgrammaticAccessToken(pat) => Some(Box::new(pat)),
+            AuthStrategy::Custom(authenticator) => Some(Box::new(authenticator.as_ref())),
+            AuthStrategy::PasswordMfa(_)
+            | AuthStrategy::ExternalBrowser(_)
+            | AuthStrategy::Okta(_) => None,
+            #[cfg(feature = "auth-oauth")]
+            AuthStrategy::OAuthClient(_) => None,
+            #[cfg(feature = "auth-wif")]
+            AuthStrategy::WorkloadIdentity(_) => None,
+        }
+    }
+}
+
+/// Asks Snowflake to start an SSO flow for `authenticator`, returning where to send the user next.
+pub(crate) async fn authenticator_request<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    authenticator: &str,
+    extra: Map<String, Value>,
+) -> Result<AuthenticatorRequestData, SnowflakeError> {
+    let opts = conn.get_opts();
+
+    let mut body = json!({
+        "data": {
+            "CLIENT_APP_ID": opts.client_app_id.as_str(),
+            "CLIENT_APP_VERSION": opts.client_app_version.as_str(),
+            "ACCOUNT_NAME": conn.account().name(),
+            "LOGIN_NAME": opts.username.as_str(),
+            "AUTHENTICATOR": authenticator,
+        }
+    });
+
+    body["data"]
+        .as_object_mut()
+        .expect("unexpected none object in authenticator request body")
+        .extend(extra);
+
+    let request = this_errors!(
+        "failed to build authenticator request",
+        http::RequestBuilder::default()
+            .connection(conn.clone())
+            .path(AUTHENTICATOR_REQUEST_PATH)
+            .headers([("ACCEPT".to_string(), "application/json".to_string())])
+            .build()
+    );
+
+    let resp = request.post::<AuthenticatorRequestResponse>(body).await?;
+
+    match resp {
+        AuthenticatorRequestResponse::Success(data) => Ok(data.data),
+        AuthenticatorRequestResponse::Failure(err) => Err(error!(err.message)),
+    }
 }
@@ -0,0 +1,68 @@
+use std::{fmt::Debug, sync::Arc};
+
+use futures_util::{FutureExt, future::BoxFuture};
+
+use crate::{
+    SnowflakeError,
+    auth::{Authenticator, AuthenticatorError, LoginRequest},
+    this_errors,
+};
+
+pub type OAuthTokenError = Box<dyn std::error::Error + Send + Sync>;
+
+type ProviderFn = dyn Fn() -> BoxFuture<'static, Result<String, OAuthTokenError>> + Send + Sync;
+
+/// Supplies OAuth access tokens for `AuthStrategy::OAuth`.
+///
+/// The provider is called on every login, including the re-login done once the master token
+/// expires, so it should return an access token that is valid at the time it is called.
+#[derive(Clone)]
+pub struct OAuthTokenProvider(Arc<ProviderFn>);
+
+impl OAuthTokenProvider {
+    /// Creates a provider from an async callback, usually one that fetches a token from your IdP.
+    pub fn new<F, Fut>(provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, OAuthTokenError>> + Send + 'static,
+    {
+        Self(Arc::new(move || provider().boxed()))
+    }
+
+    /// Creates a provider that always returns the same access token.
+    pub fn from_token(token: impl Into<String>) -> Self {
+        let token = token.into();
+        Self::new(move || {
+            let token = token.clone();
+            async move { Ok(token) }
+        })
+    }
+
+    pub(crate) async fn get_token(&self) -> Result<String, SnowflakeError> {
+        let token = this_errors!("failed to get oauth access token", (self.0)().await);
+        Ok(token)
+    }
+}
+
+impl Authenticator for OAuthTokenProvider {
+    fn authenticate<'a>(
+        &'a self,
+        request: &'a mut LoginRequest,
+    ) -> BoxFuture<'a, Result<(), AuthenticatorError>> {
+        async move {
+            // Always ask the provider, the previous access token may have expired since the last login
+            let token = self.get_token().await?;
+
+            request.set("AUTHENTICATOR", "OAUTH");
+            request.set("TOKEN", token);
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+impl Debug for OAuthTokenProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OAuthTokenProvider")
+    }
+}
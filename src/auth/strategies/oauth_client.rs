@@ -0,0 +1,399 @@
+use std::{
+    fmt::Debug,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use base64::Engine;
+use futures_util::lock::Mutex;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    SnowflakeError,
+    auth::{Secret, callback::CallbackListener},
+    connection::Connection,
+    error,
+    http::{self, client::SnowflakeHttpClient, url},
+    this_errors,
+};
+
+use super::external_browser::{BrowserOpenError, OpenerFn, open_url};
+
+#[cfg(test)]
+#[path = "./oauth_client_test.rs"]
+mod oauth_client_test;
+
+static SNOWFLAKE_TOKEN_PATH: &str = "/oauth/token-request";
+static SNOWFLAKE_AUTHORIZATION_PATH: &str = "/oauth/authorize";
+
+// Refresh access tokens a little early so they don't expire in flight
+static EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+enum Grant {
+    ClientCredentials,
+    RefreshToken(Secret),
+    AuthorizationCode,
+}
+
+#[derive(Debug)]
+struct CachedTokens {
+    access_token: Option<Secret>,
+    expires_at: Option<Instant>,
+    refresh_token: Option<Secret>,
+}
+
+impl CachedTokens {
+    fn usable_access_token(&self) -> Option<&str> {
+        let fresh = self
+            .expires_at
+            .is_none_or(|expires_at| Instant::now() + EXPIRY_MARGIN < expires_at);
+
+        self.access_token
+            .as_ref()
+            .map(Secret::expose)
+            .filter(|_| fresh)
+    }
+}
+
+/// Options for `AuthStrategy::OAuthClient`, where the driver gets OAuth access tokens itself.
+///
+/// Supports the client credentials grant, the refresh token grant and the authorization code grant
+/// with PKCE, which sends the user to the authorization server in their browser and waits for the
+/// redirect on `localhost`. Access tokens are cached and refreshed (with the refresh token if the
+/// server issued one) when they expire, so re-logins after the master token expires keep working.
+///
+/// The endpoints default to Snowflake's own OAuth server, set `token_url` and `authorization_url`
+/// for an external IdP.
+#[derive(Clone)]
+pub struct OAuthClient {
+    grant: Grant,
+    pub(crate) client_id: String,
+    pub(crate) client_secret: Option<Secret>,
+    pub(crate) scope: Option<String>,
+    pub(crate) token_url: Option<String>,
+    authorization_url: Option<String>,
+    redirect_port: u16,
+    timeout: Duration,
+    opener: Option<Arc<OpenerFn>>,
+    tokens: Arc<Mutex<CachedTokens>>,
+}
+
+impl OAuthClient {
+    fn new(grant: Grant, client_id: impl Into<String>) -> Self {
+        Self {
+            grant,
+            client_id: client_id.into(),
+            client_secret: None,
+            scope: None,
+            token_url: None,
+            authorization_url: None,
+            redirect_port: 0,
+            timeout: Duration::from_secs(120),
+            opener: None,
+            tokens: Arc::new(Mutex::new(CachedTokens {
+                access_token: None,
+                expires_at: None,
+                refresh_token: None,
+            })),
+        }
+    }
+
+    /// The client credentials grant, for service-to-service access with no user involved.
+    pub fn client_credentials(
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+    ) -> Self {
+        Self::new(Grant::ClientCredentials, client_id).client_secret(client_secret)
+    }
+
+    /// The refresh token grant, starting from a refresh token you already have.
+    pub fn refresh_token(client_id: impl Into<String>, refresh_token: impl Into<String>) -> Self {
+        Self::new(Grant::RefreshToken(Secret::new(refresh_token)), client_id)
+    }
+
+    /// The authorization code grant with PKCE, signing the user in through their browser.
+    pub fn authorization_code(client_id: impl Into<String>) -> Self {
+        Self::new(Grant::AuthorizationCode, client_id)
+    }
+
+    /// Sent with HTTP basic auth to the token endpoint. Public clients don't need one.
+    pub fn client_secret(mut self, secret: impl Into<String>) -> Self {
+        self.client_secret = Some(Secret::new(secret));
+        self
+    }
+
+    /// For Snowflake's OAuth server this is usually `session:role:<ROLE>`.
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// Defaults to `{host}/oauth/token-request`.
+    pub fn token_url(mut self, url: impl Into<String>) -> Self {
+        self.token_url = Some(url.into());
+        self
+    }
+
+    /// Defaults to `{host}/oauth/authorize`. Only used by the authorization code grant.
+    pub fn authorization_url(mut self, url: impl Into<String>) -> Self {
+        self.authorization_url = Some(url.into());
+        self
+    }
+
+    /// Port the localhost redirect listener binds to. Defaults to `0`, a random free port.
+    /// Authorization servers that require an exact redirect URI need this set.
+    pub fn redirect_port(mut self, port: u16) -> Self {
+        self.redirect_port = port;
+        self
+    }
+
+    /// How long to wait for the browser to redirect back. Defaults to 120 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Replaces how the authorization URL is opened. By default the system browser is launched, and
+    /// the login fails with the URL in the error if that fails.
+    pub fn open_with<F>(mut self, opener: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), BrowserOpenError> + Send + Sync + 'static,
+    {
+        self.opener = Some(Arc::new(opener));
+        self
+    }
+
+    pub(crate) fn is_client_credentials(&self) -> bool {
+        matches!(self.grant, Grant::ClientCredentials)
+    }
+
+    /// Returns a cached access token, refreshing or requesting a new one when it has expired.
+    pub(crate) async fn access_token<C: SnowflakeHttpClient>(
+        &self,
+        conn: &Connection<C>,
+    ) -> Result<String, SnowflakeError> {
+        // Held across the request, so sessions logging in at once share a single token request
+        let mut tokens = self.tokens.lock().await;
+
+        if let Some(token) = tokens.usable_access_token() {
+            return Ok(token.to_string());
+        }
+
+        let refreshed = match tokens.refresh_token.clone() {
+            Some(refresh_token) => self.refresh(conn, refresh_token.expose()).await.ok(),
+            None => None,
+        };
+
+        let resp = match refreshed {
+            Some(resp) => resp,
+            None => match &self.grant {
+                Grant::ClientCredentials => {
+                    self.request_token(conn, vec![("grant_type", "client_credentials".into())])
+                        .await?
+                }
+                Grant::RefreshToken(refresh_token) => {
+                    self.refresh(conn, refresh_token.expose()).await?
+                }
+                Grant::AuthorizationCode => self.authorize(conn).await?,
+            },
+        };
+
+        tokens.expires_at = resp
+            .expires_in
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        tokens.refresh_token = resp
+            .refresh_token
+            .map(Secret::from)
+            .or(tokens.refresh_token.take());
+        tokens.access_token = Some(Secret::new(resp.access_token.clone()));
+
+        Ok(resp.access_token)
+    }
+
+    /// Forgets the cached access token, e.g. after Snowflake rejected it.
+    pub(crate) async fn invalidate_access_token(&self) {
+        self.tokens.lock().await.access_token = None;
+    }
+
+    async fn refresh<C: SnowflakeHttpClient>(
+        &self,
+        conn: &Connection<C>,
+        refresh_token: &str,
+    ) -> Result<TokenResponse, SnowflakeError> {
+        self.request_token(
+            conn,
+            vec![
+                ("grant_type", "refresh_token".into()),
+                ("refresh_token", refresh_token.into()),
+            ],
+        )
+        .await
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc7636
+    async fn authorize<C: SnowflakeHttpClient>(
+        &self,
+        conn: &Connection<C>,
+    ) -> Result<TokenResponse, SnowflakeError> {
+        let listener = CallbackListener::bind(self.redirect_port)?;
+        let redirect_uri = format!("http://127.0.0.1:{}", listener.port());
+
+        let verifier = pkce_verifier();
+        let challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(Sha256::digest(verifier.as_bytes()));
+        let state = uuid::Uuid::new_v4().simple().to_string();
+
+        let mut params = vec![
+            ("response_type".to_string(), "code".to_string()),
+            ("client_id".to_string(), self.client_id.clone()),
+            ("redirect_uri".to_string(), redirect_uri.clone()),
+            ("code_challenge".to_string(), challenge),
+            ("code_challenge_method".to_string(), "S256".to_string()),
+            ("state".to_string(), state.clone()),
+        ];
+        if let Some(scope) = self.scope.as_ref() {
+            params.push(("scope".to_string(), scope.clone()));
+        }
+
+        let authorization_url = self
+            .authorization_url
+            .clone()
+            .unwrap_or_else(|| format!("{}{}", conn.base_url(), SNOWFLAKE_AUTHORIZATION_PATH));
+        let authorization_url = this_errors!(
+            "failed to construct authorization url",
+            url::construct_url(&authorization_url, &params)
+        );
+
+        open_url(self.opener.as_deref(), &authorization_url)?;
+
+        let params = listener.wait_for("code", self.timeout).await?;
+
+        // Make sure this redirect answers the request we just made
+        if params.get("state") != Some(&state) {
+            return Err(error!("oauth redirect has a mismatched state"));
+        }
+
+        let code = params
+            .get("code")
+            .ok_or(error!("missing code in oauth redirect"))?;
+
+        self.request_token(
+            conn,
+            vec![
+                ("grant_type", "authorization_code".into()),
+                ("code", code.clone()),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", verifier),
+            ],
+        )
+        .await
+    }
+
+    async fn request_token<C: SnowflakeHttpClient>(
+        &self,
+        conn: &Connection<C>,
+        params: Vec<(&str, String)>,
+    ) -> Result<TokenResponse, SnowflakeError> {
+        let mut params = params
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect::<Vec<_>>();
+
+        let mut headers = vec![("ACCEPT".to_string(), "application/json".to_string())];
+
+        match self.client_secret.as_ref() {
+            Some(secret) => {
+                let credentials = base64::engine::general_purpose::STANDARD.encode(format!(
+                    "{}:{}",
+                    self.client_id,
+                    secret.expose()
+                ));
+                headers.push(("Authorization".to_string(), format!("Basic {credentials}")));
+            }
+            None => params.push(("client_id".to_string(), self.client_id.clone())),
+        }
+
+        if let Some(scope) = self.scope.as_ref() {
+            params.push(("scope".to_string(), scope.clone()));
+        }
+
+        let token_url = self
+            .token_url
+            .clone()
+            .unwrap_or_else(|| format!("{}{}", conn.base_url(), SNOWFLAKE_TOKEN_PATH));
+
+        let request = this_errors!(
+            "failed to build oauth token request",
+            http::RequestBuilder::default()
+                .connection(conn.clone())
+                .full_url(token_url)
+                .headers(
+                    headers
+                        .into_iter()
+                        .collect::<std::collections::HashMap<_, _>>()
+                )
+                .build()
+        );
+
+        match request.post_form::<TokenEndpointResponse>(&params).await? {
+            TokenEndpointResponse::Success(resp) => Ok(resp),
+            TokenEndpointResponse::Failure(err) => Err(error!(
+                "oauth token endpoint returned an error",
+                format!(
+                    "{}: {}",
+                    err.error,
+                    err.error_description.unwrap_or_default()
+                )
+            )),
+        }
+    }
+}
+
+impl Debug for OAuthClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let grant = match self.grant {
+            Grant::ClientCredentials => "client_credentials",
+            Grant::RefreshToken(_) => "refresh_token",
+            Grant::AuthorizationCode => "authorization_code",
+        };
+
+        f.debug_struct("OAuthClient")
+            .field("grant", &grant)
+            .field("client_id", &self.client_id)
+            .field("scope", &self.scope)
+            .field("token_url", &self.token_url)
+            .finish_non_exhaustive()
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6749#section-5.1
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TokenEndpointResponse {
+    Success(TokenResponse),
+    Failure(TokenErrorResponse),
+}
+
+/// 43 characters of unreserved characters, the shortest verifier RFC 7636 allows.
+fn pkce_verifier() -> String {
+    let mut bytes = [0u8; 32];
+    bytes[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    bytes[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex as StdMutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use serde_json::json;
+
+#[cfg(test)]
+use super::*;
+use crate::{
+    auth::AuthStrategy,
+    test_utils::{
+        MockRequest, MockResponse, MockServer, TestClient, login_failure, login_success,
+        send_request, test_opts,
+    },
+};
+
+fn form(req: &MockRequest) -> HashMap<String, String> {
+    url::parse_query(&String::from_utf8_lossy(&req.body))
+        .unwrap()
+        .into_iter()
+        .collect()
+}
+
+fn mock_snowflake() -> MockServer {
+    MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        _ => MockResponse::status(404),
+    })
+}
+
+#[tokio::test]
+async fn test_client_credentials_token_is_cached() {
+    let idp = MockServer::start(|req| {
+        let form = form(req);
+        if form["grant_type"] == "client_credentials"
+            && req.header("Authorization") == Some("Basic Y2xpZW50OnNlY3JldA==")
+        {
+            MockResponse::json(json!({
+                "access_token": "access-1",
+                "token_type": "Bearer",
+                "expires_in": 3600
+            }))
+        } else {
+            MockResponse::json(json!({ "error": "invalid_client" }))
+        }
+    });
+    let snowflake = mock_snowflake();
+
+    let client = OAuthClient::client_credentials("client", "secret")
+        .scope("session:role:ANALYST")
+        .token_url(format!("{}/oauth2/token", idp.url()));
+
+    let mut opts = test_opts(&snowflake.url(), AuthStrategy::OAuthClient(client));
+    opts.pool_size = 2;
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in with client credentials");
+
+    let token_requests = idp.requests_to("/oauth2/token");
+    assert_eq!(token_requests.len(), 1);
+    assert_eq!(form(&token_requests[0])["scope"], "session:role:ANALYST");
+
+    for login in snowflake.requests_to("/session/v1/login-request") {
+        let data = &login.json()["data"];
+        assert_eq!(data["AUTHENTICATOR"], "OAUTH");
+        assert_eq!(data["TOKEN"], "access-1");
+    }
+}
+
+#[tokio::test]
+async fn test_expired_access_token_is_refreshed() {
+    let issued = AtomicUsize::new(0);
+    let idp = MockServer::start(move |req| {
+        let form = form(req);
+        let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if form["grant_type"] == "refresh_token" && form["refresh_token"] == format!("refresh-{n}")
+        {
+            // Expires within the refresh margin, so every login refreshes
+            MockResponse::json(json!({
+                "access_token": format!("access-{n}"),
+                "expires_in": 30,
+                "refresh_token": format!("refresh-{}", n + 1)
+            }))
+        } else {
+            MockResponse::json(json!({ "error": "invalid_grant" }))
+        }
+    });
+    let snowflake = mock_snowflake();
+
+    let client = OAuthClient::refresh_token("client", "refresh-1").token_url(idp.url());
+
+    let mut opts = test_opts(&snowflake.url(), AuthStrategy::OAuthClient(client));
+    opts.pool_size = 2;
+
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in with a refresh token");
+
+    // The rotated refresh token from the first response is used for the second
+    let token_requests = idp.requests();
+    assert_eq!(token_requests.len(), 2);
+    assert_eq!(form(&token_requests[0])["client_id"], "client");
+    assert_eq!(form(&token_requests[1])["refresh_token"], "refresh-2");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins[0].json()["data"]["TOKEN"], "access-1");
+    assert_eq!(logins[1].json()["data"]["TOKEN"], "access-2");
+}
+
+#[tokio::test]
+async fn test_authorization_code_with_pkce() {
+    let challenge = Arc::new(StdMutex::new(String::new()));
+    let idp = MockServer::start(|req| {
+        let form = form(req);
+        if form["grant_type"] == "authorization_code" && form["code"] == "auth-code" {
+            MockResponse::json(json!({ "access_token": "access-1", "expires_in": 3600 }))
+        } else {
+            MockResponse::json(json!({ "error": "invalid_grant" }))
+        }
+    });
+    let snowflake = mock_snowflake();
+
+    let client = OAuthClient::authorization_code("client")
+        .authorization_url(format!("{}/authorize", idp.url()))
+        .token_url(format!("{}/token", idp.url()))
+        .timeout(Duration::from_secs(10))
+        .open_with({
+            let challenge = challenge.clone();
+            move |authorization_url| {
+                // Act as the user's browser: approve, then get redirected back with a code
+                let (_, query) = authorization_url.split_once('?').unwrap();
+                let params: HashMap<_, _> = url::parse_query(query).unwrap().into_iter().collect();
+                assert_eq!(params["code_challenge_method"], "S256");
+                *challenge.lock().unwrap() = params["code_challenge"].clone();
+
+                let callback = url::construct_url(
+                    &format!("{}/", params["redirect_uri"]),
+                    &[
+                        ("code".to_string(), "auth-code".to_string()),
+                        ("state".to_string(), params["state"].clone()),
+                    ],
+                )
+                .unwrap();
+                std::thread::spawn(move || {
+                    send_request("GET", &callback, &HashMap::new(), &[]).unwrap();
+                });
+                Ok(())
+            }
+        });
+
+    let opts = test_opts(&snowflake.url(), AuthStrategy::OAuthClient(client));
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in with the authorization code flow");
+
+    let token_requests = idp.requests_to("/token");
+    assert_eq!(token_requests.len(), 1);
+
+    let verifier = form(&token_requests[0])["code_verifier"].clone();
+    let expected = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .encode(Sha256::digest(verifier.as_bytes()));
+    assert_eq!(*challenge.lock().unwrap(), expected);
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins[0].json()["data"]["TOKEN"], "access-1");
+}
+
+#[tokio::test]
+async fn test_rejected_access_token_is_replaced() {
+    let issued = AtomicUsize::new(0);
+    let idp = MockServer::start(move |_| {
+        let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+        MockResponse::json(json!({ "access_token": format!("access-{n}"), "expires_in": 3600 }))
+    });
+    let snowflake = MockServer::start(|req| {
+        if req.json()["data"]["TOKEN"] == "access-2" {
+            login_success()
+        } else {
+            login_failure("390303", "Invalid OAuth access token.")
+        }
+    });
+
+    let client = OAuthClient::client_credentials("client", "secret").token_url(idp.url());
+
+    let opts = test_opts(&snowflake.url(), AuthStrategy::OAuthClient(client));
+    let _pool = opts
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in with a replacement access token");
+
+    assert_eq!(idp.requests().len(), 2);
+    assert_eq!(snowflake.requests().len(), 2);
+}
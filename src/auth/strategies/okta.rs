@@ -0,0 +1,207 @@
+use serde::Deserialize;
+use serde_json::{Map, json};
+
+use crate::{
+    SnowflakeError,
+    auth::Secret,
+    connection::Connection,
+    error,
+    http::{self, client::SnowflakeHttpClient, url},
+    this_errors,
+};
+
+#[cfg(test)]
+#[path = "./okta_test.rs"]
+mod okta_test;
+
+/// Options for `AuthStrategy::Okta`, Snowflake's native Okta SSO.
+///
+/// Unlike `ExternalBrowser` this doesn't need a browser, the username and password are sent to
+/// Okta directly, so it works in headless jobs.
+#[derive(Clone, Debug)]
+pub struct Okta {
+    url: String,
+    password: Secret,
+}
+
+impl Okta {
+    /// `url` is your Okta endpoint, for example `https://<org>.okta.com`.
+    pub fn new(url: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            password: Secret::new(password),
+        }
+    }
+
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OktaAuthnResponse {
+    session_token: Option<String>,
+    cookie_token: Option<String>,
+    error_summary: Option<String>,
+}
+
+// Taken from:
+// https://github.com/snowflakedb/gosnowflake/blob/master/authokta.go
+/// Runs the Okta handshake and returns the raw SAML response to log in with.
+pub(crate) async fn authenticate<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    okta: &Okta,
+) -> Result<String, SnowflakeError> {
+    let opts = conn.get_opts();
+
+    // Step 1: Ask Snowflake where to authenticate
+    let data = super::authenticator_request(conn, &okta.url, Map::new()).await?;
+
+    let token_url = data
+        .token_url
+        .ok_or(error!("missing tokenUrl in authenticator response"))?;
+
+    let sso_url = data
+        .sso_url
+        .ok_or(error!("missing ssoUrl in authenticator response"))?;
+
+    // Step 2: Never send the password anywhere other than the Okta endpoint we were configured with
+    if !same_origin(&okta.url, &token_url) || !same_origin(&okta.url, &sso_url) {
+        return Err(error!(
+            "the specified okta authenticator is not accepted by your snowflake account configuration"
+        ));
+    }
+
+    // Step 3: Authenticate with Okta for a one-time token
+    let request = this_errors!(
+        "failed to build okta authn request",
+        http::RequestBuilder::default()
+            .connection(conn.clone())
+            .full_url(token_url)
+            .headers([("ACCEPT".to_string(), "application/json".to_string())])
+            .build()
+    );
+
+    let authn = request
+        .post::<OktaAuthnResponse>(json!({
+            "username": opts.username.as_str(),
+            "password": okta.password.expose(),
+        }))
+        .await?;
+
+    let one_time_token = match (authn.session_token, authn.cookie_token) {
+        (Some(token), _) | (None, Some(token)) => token,
+        (None, None) => {
+            return Err(error!(
+                "okta did not return a one-time token",
+                authn.error_summary
+            ));
+        }
+    };
+
+    // Step 4: Exchange the one-time token for the SAML response
+    let params = [
+        ("RelayState".to_string(), "/some/deep/link".to_string()),
+        ("onetimetoken".to_string(), one_time_token),
+    ];
+    let query = this_errors!(
+        "failed to construct sso url",
+        url::construct_url("", &params)
+    );
+    let full_sso_url = if sso_url.contains('?') {
+        format!("{}&{}", sso_url, &query[1..])
+    } else {
+        format!("{}{}", sso_url, query)
+    };
+
+    let request = this_errors!(
+        "failed to build okta sso request",
+        http::RequestBuilder::default()
+            .connection(conn.clone())
+            .full_url(full_sso_url)
+            .headers([("ACCEPT".to_string(), "*/*".to_string())])
+            .build()
+    );
+
+    let saml_response = this_errors!(
+        "okta sso response is not valid utf-8",
+        String::from_utf8(request.get_as_bytes().await?)
+    );
+
+    // Step 5: Make sure the assertion is meant for this Snowflake account before handing it over
+    let postback_url = postback_url(&saml_response).ok_or(error!(
+        "failed to find the postback url in the okta saml response"
+    ))?;
+
+    if !same_origin(&postback_url, &conn.base_url()) {
+        return Err(error!(
+            "the destination url in the saml assertion does not match this snowflake account"
+        ));
+    }
+
+    Ok(saml_response)
+}
+
+fn same_origin(a: &str, b: &str) -> bool {
+    match (url::origin(a), url::origin(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Finds the `action` of the SAML form, which is where the browser would have posted it.
+fn postback_url(html: &str) -> Option<String> {
+    let form_start = html.find("<form")?;
+    let form = &html[form_start..];
+    let form = &form[..form.find('>')?];
+
+    let action_start = form.find("action=\"")? + "action=\"".len();
+    let action = &form[action_start..];
+    let action = &action[..action.find('"')?];
+
+    Some(unescape_html(action))
+}
+
+fn unescape_html(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+
+        match decoded {
+            Some(c) => {
+                output.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
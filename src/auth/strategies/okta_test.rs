@@ -0,0 +1,118 @@
+use std::sync::{Arc, OnceLock};
+
+#[cfg(test)]
+use super::*;
+use crate::{
+    auth::AuthStrategy,
+    test_utils::{MockResponse, MockServer, TestClient, login_success, test_opts},
+};
+
+fn escape_url(url: &str) -> String {
+    url.replace(':', "&#x3a;").replace('/', "&#x2f;")
+}
+
+/// Starts a stand-in Okta and Snowflake that know about each other.
+/// `token_url` overrides the token url Snowflake hands out.
+fn mock_okta_and_snowflake(token_url: Option<String>) -> (MockServer, MockServer) {
+    let snowflake_url = Arc::new(OnceLock::<String>::new());
+    let okta_url = Arc::new(OnceLock::<String>::new());
+
+    let okta = {
+        let snowflake_url = snowflake_url.clone();
+        MockServer::start(move |req| match req.path.as_str() {
+            "/api/v1/authn" => {
+                let body = req.json();
+                if body["username"] == "TEST_USER" && body["password"] == "hunter2" {
+                    MockResponse::json(json!({ "sessionToken": "one-time-token" }))
+                } else {
+                    MockResponse::json(json!({ "errorSummary": "Authentication failed" }))
+                }
+            }
+            "/app/snowflake/sso/saml"
+                if req.query_param("onetimetoken") == Some("one-time-token") =>
+            {
+                let action = escape_url(&format!("{}/fed/login", snowflake_url.get().unwrap()));
+                MockResponse::html(format!(
+                    "<html><body><form method=\"post\" action=\"{action}\">\
+                     <input type=\"hidden\" name=\"SAMLResponse\" value=\"PHNhbWw+\"/></form></body></html>"
+                ))
+            }
+            _ => MockResponse::status(404),
+        })
+    };
+
+    let snowflake = {
+        let okta_url = okta_url.clone();
+        MockServer::start(move |req| match req.path.as_str() {
+            "/session/authenticator-request" => {
+                let okta_url = okta_url.get().unwrap();
+                MockResponse::json(json!({
+                    "data": {
+                        "tokenUrl": token_url.clone().unwrap_or(format!("{okta_url}/api/v1/authn")),
+                        "ssoUrl": format!("{okta_url}/app/snowflake/sso/saml"),
+                    },
+                    "code": null,
+                    "message": null,
+                    "success": true
+                }))
+            }
+            "/session/v1/login-request" => login_success(),
+            _ => MockResponse::status(404),
+        })
+    };
+
+    snowflake_url.set(snowflake.url()).unwrap();
+    okta_url.set(okta.url()).unwrap();
+
+    (okta, snowflake)
+}
+
+#[tokio::test]
+async fn test_okta_login() {
+    let (okta, snowflake) = mock_okta_and_snowflake(None);
+
+    let strategy = AuthStrategy::Okta(Okta::new(format!("{}/", okta.url()), "hunter2"));
+    let _pool = test_opts(&snowflake.url(), strategy)
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to log in through okta");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 1);
+
+    let data = &logins[0].json()["data"];
+    assert_eq!(data["AUTHENTICATOR"], okta.url().as_str());
+    assert!(
+        data["RAW_SAML_RESPONSE"]
+            .as_str()
+            .unwrap()
+            .contains("SAMLResponse")
+    );
+    assert!(data.get("PASSWORD").is_none());
+}
+
+#[tokio::test]
+async fn test_okta_rejects_untrusted_token_url() {
+    let attacker = MockServer::start(|_| MockResponse::json(json!({ "sessionToken": "stolen" })));
+    let (okta, snowflake) =
+        mock_okta_and_snowflake(Some(format!("{}/api/v1/authn", attacker.url())));
+
+    let strategy = AuthStrategy::Okta(Okta::new(okta.url(), "hunter2"));
+    let result = test_opts(&snowflake.url(), strategy)
+        .connect_json_with_client::<TestClient>()
+        .await;
+
+    assert!(result.is_err());
+    assert!(attacker.requests().is_empty());
+    assert!(okta.requests().is_empty());
+}
+
+#[test]
+fn test_postback_url() {
+    let html = "<html><form method=\"post\" action=\"https&#x3a;&#x2f;&#x2f;acct.snowflakecomputing.com&#x2f;fed&#x2f;login\"></form></html>";
+    assert_eq!(
+        postback_url(html).as_deref(),
+        Some("https://acct.snowflakecomputing.com/fed/login")
+    );
+    assert!(postback_url("<html>no form here</html>").is_none());
+}
@@ -0,0 +1,27 @@
+use std::fmt::Debug;
+
+use futures_util::{FutureExt, future::BoxFuture};
+
+use crate::auth::{Authenticator, AuthenticatorError, LoginRequest};
+
+/// `AuthStrategy::Password`, Snowflake's default username and password login.
+pub(crate) struct PasswordAuthenticator<'a>(pub &'a str);
+
+impl Authenticator for PasswordAuthenticator<'_> {
+    fn authenticate<'a>(
+        &'a self,
+        request: &'a mut LoginRequest,
+    ) -> BoxFuture<'a, Result<(), AuthenticatorError>> {
+        async move {
+            request.set("PASSWORD", self.0);
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+impl Debug for PasswordAuthenticator<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PasswordAuthenticator")
+    }
+}
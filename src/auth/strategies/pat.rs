@@ -0,0 +1,78 @@
+use std::time::SystemTime;
+
+use futures_util::{FutureExt, future::BoxFuture};
+
+use crate::{
+    SnowflakeError,
+    auth::{Authenticator, AuthenticatorError, LoginRequest, Secret},
+    error,
+};
+
+/// A Snowflake programmatic access token (PAT) for `AuthStrategy::ProgrammaticAccessToken`.
+///
+/// PATs are long-lived but do expire. If you know when yours expires, set it with
+/// [`ProgrammaticAccessToken::expires_at`] and logins will fail with a clear error instead of
+/// sending an expired token to Snowflake.
+#[derive(Clone, Debug)]
+pub struct ProgrammaticAccessToken {
+    token: Secret,
+    expires_at: Option<SystemTime>,
+}
+
+impl ProgrammaticAccessToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: Secret::new(token),
+            expires_at: None,
+        }
+    }
+
+    /// Sets when this token stops being valid.
+    pub fn expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| SystemTime::now() >= expires_at)
+    }
+
+    pub(crate) fn get_token(&self) -> Result<&str, SnowflakeError> {
+        if self.is_expired() {
+            return Err(error!(
+                "programmatic access token has expired, generate a new one for this user"
+            ));
+        }
+
+        Ok(self.token.expose())
+    }
+}
+
+impl Authenticator for ProgrammaticAccessToken {
+    fn authenticate<'a>(
+        &'a self,
+        request: &'a mut LoginRequest,
+    ) -> BoxFuture<'a, Result<(), AuthenticatorError>> {
+        async move {
+            let token = self.get_token()?;
+
+            request.set("AUTHENTICATOR", "PROGRAMMATIC_ACCESS_TOKEN");
+            request.set("TOKEN", token);
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+impl From<String> for ProgrammaticAccessToken {
+    fn from(token: String) -> Self {
+        Self::new(token)
+    }
+}
+
+impl From<&str> for ProgrammaticAccessToken {
+    fn from(token: &str) -> Self {
+        Self::new(token)
+    }
+}
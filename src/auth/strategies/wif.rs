@@ -0,0 +1,558 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    SnowflakeError,
+    auth::Secret,
+    connection::Connection,
+    error,
+    http::{self, client::SnowflakeHttpClient},
+    this_errors,
+};
+
+#[cfg(test)]
+#[path = "./wif_test.rs"]
+mod wif_test;
+
+static DEFAULT_METADATA_URL: &str = "http://169.254.169.254";
+static DEFAULT_ECS_CREDENTIALS_URL: &str = "http://169.254.170.2";
+// Long enough for the metadata requests of one login
+static IMDS_TOKEN_TTL_SECONDS: &str = "60";
+static SNOWFLAKE_AUDIENCE: &str = "snowflakecomputing.com";
+// The Entra ID application Snowflake accepts managed identity tokens for
+static DEFAULT_AZURE_RESOURCE: &str = "api://fd3f753b-eed3-462c-b6a7-a4b5bb650aad";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WorkloadIdentityProvider {
+    Aws,
+    Azure,
+    Gcp,
+    Oidc,
+}
+
+impl WorkloadIdentityProvider {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            WorkloadIdentityProvider::Aws => "AWS",
+            WorkloadIdentityProvider::Azure => "AZURE",
+            WorkloadIdentityProvider::Gcp => "GCP",
+            WorkloadIdentityProvider::Oidc => "OIDC",
+        }
+    }
+}
+
+/// AWS credentials used to sign the `GetCallerIdentity` attestation.
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: Secret,
+    pub session_token: Option<Secret>,
+}
+
+impl std::fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Options for `AuthStrategy::WorkloadIdentity`, workload identity federation (WIF).
+///
+/// Proves the identity of the cloud workload this runs on to Snowflake, so no long-lived secret
+/// has to be stored with it. A fresh attestation is fetched from the platform on every login.
+///
+/// - AWS: a signed `sts:GetCallerIdentity` request. Credentials come from the environment, the
+///   ECS container endpoint or the EC2 instance metadata service (IMDSv1).
+/// - Azure: a managed identity token from the instance metadata service, or the App Service /
+///   Functions identity endpoint.
+/// - GCP: an identity token from the metadata server.
+/// - OIDC: a token read from a file, e.g. a Kubernetes projected service account token.
+#[derive(Clone, Debug)]
+pub struct WorkloadIdentity {
+    provider: WorkloadIdentityProvider,
+    metadata_url: String,
+    aws_region: Option<String>,
+    aws_credentials: Option<AwsCredentials>,
+    azure_resource: String,
+    azure_client_id: Option<String>,
+    token_file: Option<PathBuf>,
+}
+
+impl WorkloadIdentity {
+    fn new(provider: WorkloadIdentityProvider) -> Self {
+        Self {
+            provider,
+            metadata_url: DEFAULT_METADATA_URL.to_string(),
+            aws_region: None,
+            aws_credentials: None,
+            azure_resource: DEFAULT_AZURE_RESOURCE.to_string(),
+            azure_client_id: None,
+            token_file: None,
+        }
+    }
+
+    pub fn aws() -> Self {
+        Self::new(WorkloadIdentityProvider::Aws)
+    }
+
+    pub fn azure() -> Self {
+        Self::new(WorkloadIdentityProvider::Azure)
+    }
+
+    pub fn gcp() -> Self {
+        Self::new(WorkloadIdentityProvider::Gcp)
+    }
+
+    /// Reads an OIDC token from `path` on every login, so rotated tokens are picked up.
+    pub fn oidc_token_file(path: impl Into<PathBuf>) -> Self {
+        Self {
+            token_file: Some(path.into()),
+            ..Self::new(WorkloadIdentityProvider::Oidc)
+        }
+    }
+
+    pub fn provider(&self) -> WorkloadIdentityProvider {
+        self.provider
+    }
+
+    /// Base URL of the instance metadata service. Defaults to `http://169.254.169.254`.
+    pub fn metadata_url(mut self, url: impl Into<String>) -> Self {
+        self.metadata_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// AWS region of the STS endpoint. Defaults to `AWS_REGION`, then the instance's region.
+    pub fn aws_region(mut self, region: impl Into<String>) -> Self {
+        self.aws_region = Some(region.into());
+        self
+    }
+
+    /// Sign with these credentials instead of discovering them from the environment.
+    pub fn aws_credentials(mut self, credentials: AwsCredentials) -> Self {
+        self.aws_credentials = Some(credentials);
+        self
+    }
+
+    /// The Entra ID resource the Azure token is requested for. Defaults to Snowflake's application.
+    pub fn azure_resource(mut self, resource: impl Into<String>) -> Self {
+        self.azure_resource = resource.into();
+        self
+    }
+
+    /// Client id of a user-assigned managed identity.
+    pub fn azure_client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.azure_client_id = Some(client_id.into());
+        self
+    }
+}
+
+// Taken from:
+// https://github.com/snowflakedb/gosnowflake/blob/master/auth_wif.go
+/// Fetches an attestation for the workload, sent to Snowflake as the login `TOKEN`.
+pub(crate) async fn attest<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    wif: &WorkloadIdentity,
+) -> Result<String, SnowflakeError> {
+    match wif.provider {
+        WorkloadIdentityProvider::Aws => attest_aws(conn, wif).await,
+        WorkloadIdentityProvider::Azure => attest_azure(conn, wif).await,
+        WorkloadIdentityProvider::Gcp => attest_gcp(conn, wif).await,
+        WorkloadIdentityProvider::Oidc => {
+            let path = wif
+                .token_file
+                .as_ref()
+                .ok_or(error!("missing oidc token file"))?;
+
+            let token = this_errors!(
+                "failed to read oidc token file",
+                std::fs::read_to_string(path)
+            );
+
+            Ok(token.trim().to_string())
+        }
+    }
+}
+
+async fn metadata_get<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    url: String,
+    headers: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<u8>, SnowflakeError> {
+    let request = this_errors!(
+        "failed to build metadata request",
+        http::RequestBuilder::default()
+            .connection(conn.clone())
+            .full_url(url)
+            .headers(
+                headers
+                    .into_iter()
+                    .collect::<std::collections::HashMap<_, _>>()
+            )
+            .build()
+    );
+
+    request.get_as_bytes().await
+}
+
+/// Gets an IMDSv2 session token, as a header for the instance metadata requests that follow.
+///
+/// Instances can require one on every request. Like the AWS SDKs, this falls back to IMDSv1 when
+/// no token can be had, e.g. with a client that can't send `PUT` requests.
+async fn imds_token<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    wif: &WorkloadIdentity,
+) -> Option<(String, String)> {
+    let request = http::RequestBuilder::default()
+        .connection(conn.clone())
+        .full_url(format!("{}/latest/api/token", wif.metadata_url))
+        .headers(std::collections::HashMap::from([(
+            "X-aws-ec2-metadata-token-ttl-seconds".to_string(),
+            IMDS_TOKEN_TTL_SECONDS.to_string(),
+        )]))
+        .build()
+        .ok()?;
+
+    let token = String::from_utf8(request.put_as_bytes(Vec::new()).await.ok()?).ok()?;
+    let token = token.trim();
+
+    match token.is_empty() {
+        true => None,
+        false => Some(("X-aws-ec2-metadata-token".to_string(), token.to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AwsCredentialsResponse {
+    access_key_id: Option<String>,
+    secret_access_key: Option<String>,
+    token: Option<String>,
+}
+
+impl AwsCredentialsResponse {
+    fn into_credentials(self) -> Result<AwsCredentials, SnowflakeError> {
+        match (self.access_key_id, self.secret_access_key) {
+            (Some(access_key_id), Some(secret_access_key)) => Ok(AwsCredentials {
+                access_key_id,
+                secret_access_key: secret_access_key.into(),
+                session_token: self.token.map(Secret::from),
+            }),
+            _ => Err(error!("aws credentials response is missing the access key")),
+        }
+    }
+}
+
+async fn aws_credentials<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    wif: &WorkloadIdentity,
+) -> Result<AwsCredentials, SnowflakeError> {
+    if let Some(credentials) = wif.aws_credentials.clone() {
+        return Ok(credentials);
+    }
+
+    if let (Ok(access_key_id), Ok(secret_access_key)) = (
+        std::env::var("AWS_ACCESS_KEY_ID"),
+        std::env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Ok(AwsCredentials {
+            access_key_id,
+            secret_access_key: secret_access_key.into(),
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok().map(Secret::from),
+        });
+    }
+
+    // ECS and EKS Pod Identity hand out credentials through a container endpoint
+    let container_url = std::env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI")
+        .ok()
+        .or_else(|| {
+            std::env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI")
+                .ok()
+                .map(|path| format!("{DEFAULT_ECS_CREDENTIALS_URL}{path}"))
+        });
+
+    let body = if let Some(url) = container_url {
+        let headers = std::env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN")
+            .ok()
+            .map(|token| ("Authorization".to_string(), token));
+        metadata_get(conn, url, headers).await?
+    } else {
+        let token = imds_token(conn, wif).await;
+        let roles_url = format!(
+            "{}/latest/meta-data/iam/security-credentials/",
+            wif.metadata_url
+        );
+        let roles = metadata_get(conn, roles_url.clone(), token.clone()).await?;
+        let roles = String::from_utf8_lossy(&roles);
+        let role = roles
+            .lines()
+            .next()
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .ok_or(error!("no iam role is attached to this instance"))?;
+
+        metadata_get(conn, format!("{roles_url}{role}"), token).await?
+    };
+
+    let resp = this_errors!(
+        "failed to parse aws credentials",
+        serde_json::from_slice::<AwsCredentialsResponse>(&body)
+    );
+
+    resp.into_credentials()
+}
+
+async fn aws_region<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    wif: &WorkloadIdentity,
+) -> Result<String, SnowflakeError> {
+    if let Some(region) = wif.aws_region.clone() {
+        return Ok(region);
+    }
+
+    if let Ok(region) = std::env::var("AWS_REGION").or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+    {
+        return Ok(region);
+    }
+
+    let token = imds_token(conn, wif).await;
+    let url = format!("{}/latest/meta-data/placement/region", wif.metadata_url);
+    let region = this_errors!(
+        "aws region from instance metadata is not valid utf-8",
+        String::from_utf8(metadata_get(conn, url, token).await?)
+    );
+
+    Ok(region.trim().to_string())
+}
+
+async fn attest_aws<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    wif: &WorkloadIdentity,
+) -> Result<String, SnowflakeError> {
+    let region = aws_region(conn, wif).await?;
+    let credentials = aws_credentials(conn, wif).await?;
+
+    let host = if region.starts_with("cn-") {
+        format!("sts.{region}.amazonaws.com.cn")
+    } else {
+        format!("sts.{region}.amazonaws.com")
+    };
+    let query = "Action=GetCallerIdentity&Version=2011-06-15";
+
+    let mut headers = BTreeMap::new();
+    headers.insert("Host".to_string(), host.clone());
+    headers.insert(
+        "X-Snowflake-Audience".to_string(),
+        SNOWFLAKE_AUDIENCE.to_string(),
+    );
+
+    sign_v4(
+        &mut headers,
+        "POST",
+        "/",
+        query,
+        &[],
+        "sts",
+        &region,
+        &credentials,
+        SystemTime::now(),
+    );
+
+    // Snowflake replays the signed request against STS to learn who we are
+    let attestation = serde_json::json!({
+        "url": format!("https://{host}/?{query}"),
+        "method": "POST",
+        "headers": headers,
+    });
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(attestation.to_string()))
+}
+
+#[derive(Deserialize)]
+struct AzureTokenResponse {
+    access_token: Option<String>,
+    error_description: Option<String>,
+}
+
+async fn attest_azure<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    wif: &WorkloadIdentity,
+) -> Result<String, SnowflakeError> {
+    let mut params = vec![("resource".to_string(), wif.azure_resource.clone())];
+    if let Some(client_id) = wif.azure_client_id.as_ref() {
+        params.push(("client_id".to_string(), client_id.clone()));
+    }
+
+    // App Service and Azure Functions expose their own identity endpoint instead of IMDS
+    let (base, header) = match (
+        std::env::var("IDENTITY_ENDPOINT"),
+        std::env::var("IDENTITY_HEADER"),
+    ) {
+        (Ok(endpoint), Ok(secret)) => {
+            params.insert(0, ("api-version".to_string(), "2019-08-01".to_string()));
+            (endpoint, ("X-IDENTITY-HEADER".to_string(), secret))
+        }
+        _ => {
+            params.insert(0, ("api-version".to_string(), "2018-02-01".to_string()));
+            (
+                format!("{}/metadata/identity/oauth2/token", wif.metadata_url),
+                ("Metadata".to_string(), "true".to_string()),
+            )
+        }
+    };
+
+    let url = this_errors!(
+        "failed to construct azure token url",
+        http::url::construct_url(&base, &params)
+    );
+
+    let body = metadata_get(conn, url, [header]).await?;
+    let resp = this_errors!(
+        "failed to parse azure token response",
+        serde_json::from_slice::<AzureTokenResponse>(&body)
+    );
+
+    resp.access_token.ok_or(error!(
+        "azure did not return a managed identity token",
+        resp.error_description
+    ))
+}
+
+async fn attest_gcp<C: SnowflakeHttpClient>(
+    conn: &Connection<C>,
+    wif: &WorkloadIdentity,
+) -> Result<String, SnowflakeError> {
+    let url = format!(
+        "{}/computeMetadata/v1/instance/service-accounts/default/identity?audience={}",
+        wif.metadata_url, SNOWFLAKE_AUDIENCE
+    );
+
+    let body = metadata_get(
+        conn,
+        url,
+        [("Metadata-Flavor".to_string(), "Google".to_string())],
+    )
+    .await?;
+
+    let token = this_errors!(
+        "gcp identity token is not valid utf-8",
+        String::from_utf8(body)
+    );
+    let token = token.trim();
+
+    // The metadata server answers errors with plain text, make sure this is actually a JWT
+    if token.split('.').count() != 3 {
+        return Err(error!(
+            "gcp metadata server did not return an identity token"
+        ));
+    }
+
+    Ok(token.to_string())
+}
+
+/// Signs a request with AWS Signature Version 4, adding the `X-Amz-*` and `Authorization` headers.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn sign_v4(
+    headers: &mut BTreeMap<String, String>,
+    method: &str,
+    path: &str,
+    query: &str,
+    payload: &[u8],
+    service: &str,
+    region: &str,
+    credentials: &AwsCredentials,
+    now: SystemTime,
+) {
+    let (date, amz_date) = amz_dates(now);
+
+    headers.insert("X-Amz-Date".to_string(), amz_date.clone());
+    if let Some(token) = credentials.session_token.as_ref() {
+        headers.insert(
+            "X-Amz-Security-Token".to_string(),
+            token.expose().to_string(),
+        );
+    }
+
+    let canonical_headers = headers
+        .iter()
+        .map(|(k, v)| (k.to_lowercase(), v.trim().to_string()))
+        .collect::<BTreeMap<_, _>>();
+
+    let signed_headers = canonical_headers
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{method}\n{path}\n{query}\n{}\n{signed_headers}\n{}",
+        canonical_headers
+            .iter()
+            .map(|(k, v)| format!("{k}:{v}\n"))
+            .collect::<String>(),
+        hex::encode(Sha256::digest(payload)),
+    );
+
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = [date.as_str(), region, service, "aws4_request"]
+        .iter()
+        .fold(
+            format!("AWS4{}", credentials.secret_access_key.expose()).into_bytes(),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+    let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    headers.insert(
+        "Authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            credentials.access_key_id
+        ),
+    );
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Formats `time` as the `YYYYMMDD` and `YYYYMMDDTHHMMSSZ` dates used by SigV4.
+fn amz_dates(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .expect("time somehow went backwards")
+        .as_secs();
+
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (hour, minute, second) = (rem / 3600, (rem % 3600) / 60, rem % 60);
+
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let date = format!("{year:04}{month:02}{day:02}");
+    let amz_date = format!("{date}T{hour:02}{minute:02}{second:02}Z");
+    (date, amz_date)
+}
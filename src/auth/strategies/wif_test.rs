@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use serde_json::Value;
+
+#[cfg(test)]
+use super::*;
+use crate::{
+    auth::AuthStrategy,
+    test_utils::{MockResponse, MockServer, TestClient, login_success, test_opts},
+};
+
+fn mock_snowflake() -> MockServer {
+    MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        _ => MockResponse::status(404),
+    })
+}
+
+async fn login_with(wif: WorkloadIdentity) -> Result<Value, SnowflakeError> {
+    let snowflake = mock_snowflake();
+
+    let opts = test_opts(&snowflake.url(), AuthStrategy::WorkloadIdentity(wif));
+    opts.connect_json_with_client::<TestClient>().await?;
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    Ok(logins[0].json()["data"].clone())
+}
+
+// https://github.com/awslabs/aws-c-auth/tree/main/tests/aws-signing-test-suite/v4/get-vanilla
+#[test]
+fn test_sign_v4_matches_aws_test_suite() {
+    let credentials = AwsCredentials {
+        access_key_id: "AKIDEXAMPLE".into(),
+        secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+        session_token: None,
+    };
+
+    let mut headers = BTreeMap::new();
+    headers.insert("Host".to_string(), "example.amazonaws.com".to_string());
+
+    sign_v4(
+        &mut headers,
+        "GET",
+        "/",
+        "",
+        &[],
+        "service",
+        "us-east-1",
+        &credentials,
+        UNIX_EPOCH + Duration::from_secs(1_440_938_160),
+    );
+
+    assert_eq!(headers["X-Amz-Date"], "20150830T123600Z");
+    assert_eq!(
+        headers["Authorization"],
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+         SignedHeaders=host;x-amz-date, \
+         Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+    );
+}
+
+#[test]
+fn test_amz_dates() {
+    // 2024-02-29T23:59:59Z
+    let (date, amz_date) = amz_dates(UNIX_EPOCH + Duration::from_secs(1_709_251_199));
+    assert_eq!(date, "20240229");
+    assert_eq!(amz_date, "20240229T235959Z");
+}
+
+#[tokio::test]
+async fn test_aws_attestation() {
+    let wif = WorkloadIdentity::aws()
+        .aws_region("eu-west-1")
+        .aws_credentials(AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".into(),
+            secret_access_key: "secret".into(),
+            session_token: Some("session-token".into()),
+        });
+
+    let data = login_with(wif).await.expect("failed to log in with aws");
+    assert_eq!(data["AUTHENTICATOR"], "WORKLOAD_IDENTITY");
+    assert_eq!(data["PROVIDER"], "AWS");
+
+    let attestation = base64::engine::general_purpose::STANDARD
+        .decode(data["TOKEN"].as_str().unwrap())
+        .unwrap();
+    let attestation: Value = serde_json::from_slice(&attestation).unwrap();
+
+    assert_eq!(
+        attestation["url"],
+        "https://sts.eu-west-1.amazonaws.com/?Action=GetCallerIdentity&Version=2011-06-15"
+    );
+    assert_eq!(attestation["method"], "POST");
+
+    let headers = &attestation["headers"];
+    assert_eq!(headers["Host"], "sts.eu-west-1.amazonaws.com");
+    assert_eq!(headers["X-Snowflake-Audience"], "snowflakecomputing.com");
+    assert_eq!(headers["X-Amz-Security-Token"], "session-token");
+
+    let authorization = headers["Authorization"].as_str().unwrap();
+    assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+    assert!(authorization.contains("/eu-west-1/sts/aws4_request"));
+    assert!(
+        authorization
+            .contains("SignedHeaders=host;x-amz-date;x-amz-security-token;x-snowflake-audience")
+    );
+}
+
+#[tokio::test]
+async fn test_aws_instance_metadata() {
+    // Answers like an instance that requires IMDSv2
+    let imds = MockServer::start(|req| {
+        if req.method == "PUT" {
+            return match (
+                req.path.as_str(),
+                req.header("X-aws-ec2-metadata-token-ttl-seconds"),
+            ) {
+                ("/latest/api/token", Some(_)) => MockResponse::html("imds-token"),
+                _ => MockResponse::status(400),
+            };
+        }
+        if req.header("X-aws-ec2-metadata-token") != Some("imds-token") {
+            return MockResponse::status(401);
+        }
+
+        match req.path.as_str() {
+            "/latest/meta-data/placement/region" => MockResponse::html("eu-west-1"),
+            "/latest/meta-data/iam/security-credentials/" => MockResponse::html("test-role\n"),
+            "/latest/meta-data/iam/security-credentials/test-role" => {
+                MockResponse::json(serde_json::json!({
+                    "AccessKeyId": "AKIDEXAMPLE",
+                    "SecretAccessKey": "secret",
+                    "Token": "session-token",
+                }))
+            }
+            _ => MockResponse::status(404),
+        }
+    });
+
+    let data = login_with(WorkloadIdentity::aws().metadata_url(imds.url()))
+        .await
+        .expect("failed to log in with aws instance metadata");
+
+    let attestation = base64::engine::general_purpose::STANDARD
+        .decode(data["TOKEN"].as_str().unwrap())
+        .unwrap();
+    let attestation: Value = serde_json::from_slice(&attestation).unwrap();
+
+    let headers = &attestation["headers"];
+    assert_eq!(headers["Host"], "sts.eu-west-1.amazonaws.com");
+    assert_eq!(headers["X-Amz-Security-Token"], "session-token");
+    assert!(
+        headers["Authorization"]
+            .as_str()
+            .unwrap()
+            .starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/")
+    );
+
+    assert_eq!(imds.requests_to("/latest/api/token").len(), 2);
+}
+
+#[tokio::test]
+async fn test_azure_attestation() {
+    let imds = MockServer::start(|req| {
+        if req.path == "/metadata/identity/oauth2/token" && req.header("Metadata") == Some("true") {
+            MockResponse::json(serde_json::json!({ "access_token": "azure-token" }))
+        } else {
+            MockResponse::status(400)
+        }
+    });
+
+    let wif = WorkloadIdentity::azure()
+        .metadata_url(imds.url())
+        .azure_client_id("client-id");
+
+    let data = login_with(wif).await.expect("failed to log in with azure");
+    assert_eq!(data["PROVIDER"], "AZURE");
+    assert_eq!(data["TOKEN"], "azure-token");
+
+    let requests = imds.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].query_param("resource"),
+        Some("api://fd3f753b-eed3-462c-b6a7-a4b5bb650aad")
+    );
+    assert_eq!(requests[0].query_param("client_id"), Some("client-id"));
+}
+
+#[tokio::test]
+async fn test_gcp_attestation() {
+    let metadata = MockServer::start(|req| {
+        if req.header("Metadata-Flavor") == Some("Google") {
+            MockResponse::html("header.payload.signature\n")
+        } else {
+            MockResponse::html("Missing Metadata-Flavor:Google header.")
+        }
+    });
+
+    let data = login_with(WorkloadIdentity::gcp().metadata_url(metadata.url()))
+        .await
+        .expect("failed to log in with gcp");
+    assert_eq!(data["PROVIDER"], "GCP");
+    assert_eq!(data["TOKEN"], "header.payload.signature");
+
+    let requests = metadata.requests();
+    assert_eq!(
+        requests[0].path,
+        "/computeMetadata/v1/instance/service-accounts/default/identity"
+    );
+    assert_eq!(
+        requests[0].query_param("audience"),
+        Some("snowflakecomputing.com")
+    );
+}
+
+#[tokio::test]
+async fn test_gcp_rejects_non_token_responses() {
+    let metadata = MockServer::start(|_| MockResponse::html("Not Found"));
+
+    let result = login_with(WorkloadIdentity::gcp().metadata_url(metadata.url())).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_oidc_token_file() {
+    let path = std::env::temp_dir().join(format!(
+        "snowflakedb-rs-oidc-{}",
+        uuid::Uuid::new_v4().simple()
+    ));
+    std::fs::write(&path, "oidc-token\n").unwrap();
+
+    let data = login_with(WorkloadIdentity::oidc_token_file(&path))
+        .await
+        .expect("failed to log in with an oidc token");
+    assert_eq!(data["PROVIDER"], "OIDC");
+    assert_eq!(data["TOKEN"], "oidc-token");
+
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,322 @@
+use std::{collections::HashMap, fmt::Debug, sync::Mutex};
+
+use crate::auth::Secret;
+
+#[cfg(feature = "token-cache-file")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "token-cache-file")]
+use zeroize::{Zeroize, Zeroizing};
+
+#[cfg(feature = "token-cache-file")]
+use crate::{SnowflakeError, error, this_errors};
+
+#[cfg(all(test, feature = "token-cache-file"))]
+#[path = "./token_cache_test.rs"]
+mod token_cache_test;
+
+/// The kinds of temporary credentials Snowflake hands out that are worth keeping between logins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// Returned after SSO logins (e.g. `ExternalBrowser`), lets later logins skip the browser.
+    IdToken,
+    /// Returned after MFA logins, lets later logins skip the MFA prompt.
+    MfaToken,
+}
+
+impl TokenKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenKind::IdToken => "ID_TOKEN",
+            TokenKind::MfaToken => "MFA_TOKEN",
+        }
+    }
+}
+
+/// Identifies a cached token. Displays as `ACCOUNT:USER:KIND`, for caches that key by string.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TokenCacheKey {
+    pub account: String,
+    pub user: String,
+    pub kind: TokenKind,
+}
+
+impl TokenCacheKey {
+    pub fn new(account: &str, user: &str, kind: TokenKind) -> Self {
+        Self {
+            account: account.to_uppercase(),
+            user: user.to_uppercase(),
+            kind,
+        }
+    }
+}
+
+impl std::fmt::Display for TokenCacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.account, self.user, self.kind.as_str())
+    }
+}
+
+/// Storage for temporary credentials, consulted before starting an interactive or MFA login.
+///
+/// Caches should never fail a login, so errors are swallowed and treated as a cache miss.
+pub trait TokenCache: Send + Sync + Debug {
+    fn get(&self, key: &TokenCacheKey) -> Option<Secret>;
+    fn set(&self, key: &TokenCacheKey, token: &str);
+    fn remove(&self, key: &TokenCacheKey);
+}
+
+/// Keeps tokens for the lifetime of the pool. This is the default.
+#[derive(Debug, Default)]
+pub struct InMemoryTokenCache {
+    tokens: Mutex<HashMap<TokenCacheKey, Secret>>,
+}
+
+impl InMemoryTokenCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TokenCache for InMemoryTokenCache {
+    fn get(&self, key: &TokenCacheKey) -> Option<Secret> {
+        self.tokens.lock().unwrap().get(key).cloned()
+    }
+
+    fn set(&self, key: &TokenCacheKey, token: &str) {
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(key.clone(), Secret::new(token));
+    }
+
+    fn remove(&self, key: &TokenCacheKey) {
+        self.tokens.lock().unwrap().remove(key);
+    }
+}
+
+/// Keeps tokens in an AES-256-GCM encrypted file, so they survive between runs of your program.
+///
+/// The file is created readable by the current user only (`0600`), and on unix the cache refuses
+/// to use a file or directory that other users can access.
+#[cfg(feature = "token-cache-file")]
+pub struct FileTokenCache {
+    path: PathBuf,
+    key: [u8; 32],
+    lock: Mutex<()>,
+}
+
+#[cfg(feature = "token-cache-file")]
+impl FileTokenCache {
+    /// `key` encrypts the file. Keep it somewhere safer than next to the cache, like the OS keychain.
+    pub fn new(path: impl Into<PathBuf>, key: [u8; 32]) -> Result<Self, SnowflakeError> {
+        let path = path.into();
+
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            if !dir.exists() {
+                create_private_dir(dir)?;
+            }
+            check_permissions(dir)?;
+        }
+
+        if path.exists() {
+            check_permissions(&path)?;
+        }
+
+        Ok(Self {
+            path,
+            key,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// The platform's cache directory, e.g. `~/.cache/snowflake/credential_cache.bin` on Linux.
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = if cfg!(target_os = "windows") {
+            PathBuf::from(std::env::var_os("LOCALAPPDATA")?).join("Snowflake")
+        } else if cfg!(target_os = "macos") {
+            std::env::home_dir()?.join("Library/Caches/Snowflake")
+        } else if let Some(xdg) = std::env::var_os("XDG_CACHE_HOME") {
+            PathBuf::from(xdg).join("snowflake")
+        } else {
+            std::env::home_dir()?.join(".cache/snowflake")
+        };
+
+        Some(dir.join("credential_cache.bin"))
+    }
+
+    fn read(&self) -> Result<HashMap<String, Secret>, SnowflakeError> {
+        use aes_gcm::{Aes256Gcm, KeyInit, Nonce, aead::Aead};
+
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        check_permissions(&self.path)?;
+
+        let contents = match std::fs::read(&self.path) {
+            Ok(contents) => contents,
+            // Removed since it was checked, there's nothing cached
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => return Err(error!("failed to read token cache", err)),
+        };
+        if contents.len() < 12 {
+            return Err(error!("token cache file is corrupted"));
+        }
+
+        let (nonce, ciphertext) = contents.split_at(12);
+        let cipher = Aes256Gcm::new(&self.key.into());
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| error!("failed to decrypt token cache, the key may have changed"))?;
+
+        let tokens: HashMap<String, String> = this_errors!(
+            "failed to parse token cache",
+            serde_json::from_slice(&plaintext)
+        );
+
+        Ok(tokens
+            .into_iter()
+            .map(|(key, token)| (key, Secret::from(token)))
+            .collect())
+    }
+
+    fn write(&self, tokens: &HashMap<String, Secret>) -> Result<(), SnowflakeError> {
+        use aes_gcm::{
+            AeadCore, Aes256Gcm, KeyInit,
+            aead::{Aead, OsRng},
+        };
+        use std::io::Write;
+
+        let tokens: HashMap<&str, &str> = tokens
+            .iter()
+            .map(|(key, token)| (key.as_str(), token.expose()))
+            .collect();
+        let plaintext = Zeroizing::new(this_errors!(
+            "failed to serialise token cache",
+            serde_json::to_vec(&tokens)
+        ));
+
+        let cipher = Aes256Gcm::new(&self.key.into());
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| error!("failed to encrypt token cache"))?;
+
+        // Write next to the cache and rename over it, so a crash never leaves a half written file
+        let tmp_path = self.path.with_extension("tmp");
+        let mut file = this_errors!(
+            "failed to create token cache file",
+            private_file_options().open(&tmp_path)
+        );
+        this_errors!("failed to write token cache", file.write_all(&nonce));
+        this_errors!("failed to write token cache", file.write_all(&ciphertext));
+        this_errors!("failed to write token cache", file.sync_all());
+        this_errors!(
+            "failed to replace token cache",
+            std::fs::rename(&tmp_path, &self.path)
+        );
+
+        Ok(())
+    }
+
+    fn update(&self, f: impl FnOnce(&mut HashMap<String, Secret>)) -> Result<(), SnowflakeError> {
+        let _guard = self.lock.lock().unwrap();
+        // Never overwrite a cache that can't be read, it may be another key's or have unsafe permissions
+        let mut tokens = self.read()?;
+        f(&mut tokens);
+        self.write(&tokens)
+    }
+}
+
+#[cfg(feature = "token-cache-file")]
+impl TokenCache for FileTokenCache {
+    fn get(&self, key: &TokenCacheKey) -> Option<Secret> {
+        let _guard = self.lock.lock().unwrap();
+        self.read().ok()?.remove(&key.to_string())
+    }
+
+    fn set(&self, key: &TokenCacheKey, token: &str) {
+        let _ = self.update(|tokens| {
+            tokens.insert(key.to_string(), Secret::new(token));
+        });
+    }
+
+    fn remove(&self, key: &TokenCacheKey) {
+        let _ = self.update(|tokens| {
+            tokens.remove(&key.to_string());
+        });
+    }
+}
+
+#[cfg(feature = "token-cache-file")]
+impl Drop for FileTokenCache {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+#[cfg(feature = "token-cache-file")]
+impl Debug for FileTokenCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileTokenCache")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(feature = "token-cache-file", unix))]
+fn check_permissions(path: &Path) -> Result<(), SnowflakeError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = this_errors!(
+        "failed to read token cache permissions",
+        std::fs::metadata(path)
+    );
+
+    if metadata.permissions().mode() & 0o077 != 0 {
+        return Err(error!(
+            "token cache is accessible by other users, restrict it to the owner (chmod 600 for files, 700 for directories)",
+            path
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(all(feature = "token-cache-file", not(unix)))]
+fn check_permissions(_path: &Path) -> Result<(), SnowflakeError> {
+    Ok(())
+}
+
+#[cfg(feature = "token-cache-file")]
+fn create_private_dir(dir: &Path) -> Result<(), SnowflakeError> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    Ok(this_errors!(
+        "failed to create token cache directory",
+        builder.create(dir)
+    ))
+}
+
+#[cfg(feature = "token-cache-file")]
+fn private_file_options() -> std::fs::OpenOptions {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+}
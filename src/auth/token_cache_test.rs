@@ -0,0 +1,113 @@
+#[cfg(test)]
+use super::*;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "snowflakedb-rs-{name}-{}",
+        uuid::Uuid::new_v4().simple()
+    ));
+    create_private_dir(&dir).unwrap();
+    dir
+}
+
+fn key(kind: TokenKind) -> TokenCacheKey {
+    TokenCacheKey::new("test_account", "test_user", kind)
+}
+
+#[test]
+fn test_file_cache_roundtrip() {
+    let dir = temp_dir("roundtrip");
+    let path = dir.join("credential_cache.bin");
+
+    let cache = FileTokenCache::new(&path, [7; 32]).unwrap();
+    assert_eq!(cache.get(&key(TokenKind::IdToken)), None);
+
+    cache.set(&key(TokenKind::IdToken), "secret-id-token");
+    cache.set(&key(TokenKind::MfaToken), "secret-mfa-token");
+
+    // A fresh instance reads what the previous one wrote
+    let cache = FileTokenCache::new(&path, [7; 32]).unwrap();
+    assert_eq!(
+        cache
+            .get(&key(TokenKind::IdToken))
+            .as_ref()
+            .map(Secret::expose),
+        Some("secret-id-token")
+    );
+    assert_eq!(
+        cache
+            .get(&TokenCacheKey::new(
+                "TEST_ACCOUNT",
+                "TEST_USER",
+                TokenKind::MfaToken
+            ))
+            .as_ref()
+            .map(Secret::expose),
+        Some("secret-mfa-token")
+    );
+
+    cache.remove(&key(TokenKind::IdToken));
+    assert_eq!(cache.get(&key(TokenKind::IdToken)), None);
+    assert!(cache.get(&key(TokenKind::MfaToken)).is_some());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_file_cache_is_encrypted() {
+    let dir = temp_dir("encrypted");
+    let path = dir.join("credential_cache.bin");
+
+    let cache = FileTokenCache::new(&path, [7; 32]).unwrap();
+    cache.set(&key(TokenKind::IdToken), "secret-id-token");
+
+    let contents = std::fs::read(&path).unwrap();
+    let needle = b"secret-id-token";
+    assert!(!contents.windows(needle.len()).any(|w| w == needle));
+
+    // The wrong key can't read it, and is treated as a cache miss
+    let cache = FileTokenCache::new(&path, [8; 32]).unwrap();
+    assert_eq!(cache.get(&key(TokenKind::IdToken)), None);
+
+    // ...but doesn't overwrite what the right key cached
+    cache.set(&key(TokenKind::MfaToken), "other-mfa-token");
+    let cache = FileTokenCache::new(&path, [7; 32]).unwrap();
+    assert_eq!(
+        cache
+            .get(&key(TokenKind::IdToken))
+            .as_ref()
+            .map(Secret::expose),
+        Some("secret-id-token")
+    );
+    assert_eq!(cache.get(&key(TokenKind::MfaToken)), None);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_file_cache_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("permissions");
+    let path = dir.join("credential_cache.bin");
+
+    let cache = FileTokenCache::new(&path, [7; 32]).unwrap();
+    cache.set(&key(TokenKind::IdToken), "secret-id-token");
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    assert!(FileTokenCache::new(&path, [7; 32]).is_err());
+    assert_eq!(cache.get(&key(TokenKind::IdToken)), None);
+
+    // An unsafe file is left alone rather than replaced
+    let before = std::fs::read(&path).unwrap();
+    cache.set(&key(TokenKind::MfaToken), "secret-mfa-token");
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o644);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,270 @@
+//! Reads connections from the `connections.toml` and `config.toml` files shared by the Snowflake CLI
+//! and the official connectors.
+
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::{
+    SnowflakeConnectionOptsBuilder, SnowflakeError,
+    auth::{AuthStrategy, ExternalBrowser, OAuthTokenProvider, Okta, PasswordMfa},
+    error, this_errors,
+};
+
+#[cfg(test)]
+#[path = "./config_test.rs"]
+mod config_test;
+
+static CONNECTIONS_FILE: &str = "connections.toml";
+static CONFIG_FILE: &str = "config.toml";
+static DEFAULT_CONNECTION_NAME: &str = "default";
+
+static SNOWFLAKE_HOME_ENV: &str = "SNOWFLAKE_HOME";
+static DEFAULT_CONNECTION_NAME_ENV: &str = "SNOWFLAKE_DEFAULT_CONNECTION_NAME";
+// Same escape hatch as the Python connector, for files that are deliberately shared read-only
+static SKIP_READ_PERMISSIONS_ENV: &str = "SF_SKIP_WARNING_FOR_READ_PERMISSIONS_ON_CONFIG_FILE";
+
+impl SnowflakeConnectionOptsBuilder {
+    /// Starts a builder from a connection in `connections.toml` or `config.toml`.
+    ///
+    /// The files are looked up in `SNOWFLAKE_HOME`, then `~/.snowflake`, then the platform's config
+    /// directory (e.g. `~/.config/snowflake` on Linux). Without a `name`, the connection named by
+    /// `SNOWFLAKE_DEFAULT_CONNECTION_NAME`, then `default_connection_name` in `config.toml`, then
+    /// `default` is used. Options like `pool_size` still need to be set on the returned builder.
+    pub fn from_connections_toml(name: Option<&str>) -> Result<Self, SnowflakeError> {
+        let dir = snowflake_home().ok_or(error!(
+            "could not find the snowflake config directory, set SNOWFLAKE_HOME"
+        ))?;
+
+        Self::from_connections_toml_in(dir, name)
+    }
+
+    /// Like `from_connections_toml`, reading the files from `dir`.
+    pub fn from_connections_toml_in(
+        dir: impl AsRef<Path>,
+        name: Option<&str>,
+    ) -> Result<Self, SnowflakeError> {
+        let dir = dir.as_ref();
+
+        let config = read_toml(&dir.join(CONFIG_FILE))?.unwrap_or_default();
+        let mut connections = match config.get("connections") {
+            Some(Value::Table(connections)) => connections.clone(),
+            _ => Table::new(),
+        };
+
+        // connections.toml takes precedence over the [connections] section of config.toml
+        if let Some(file) = read_toml(&dir.join(CONNECTIONS_FILE))? {
+            connections.extend(file);
+        }
+
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => std::env::var(DEFAULT_CONNECTION_NAME_ENV)
+                .ok()
+                .or_else(|| {
+                    config
+                        .get("default_connection_name")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                })
+                .unwrap_or_else(|| DEFAULT_CONNECTION_NAME.to_string()),
+        };
+
+        let connection = connections
+            .get(&name)
+            .and_then(Value::as_table)
+            .ok_or(error!("connection not found in connections.toml", name))?;
+
+        builder_from_table(connection)
+    }
+}
+
+fn builder_from_table(
+    connection: &Table,
+) -> Result<SnowflakeConnectionOptsBuilder, SnowflakeError> {
+    let get = |key: &str| connection.get(key).and_then(Value::as_str);
+
+    let mut builder = SnowflakeConnectionOptsBuilder::default();
+
+    let account = get("account").ok_or(error!("connection is missing account"))?;
+    builder.account_id(account);
+
+    if let Some(user) = get("user") {
+        builder.username(user);
+    }
+    if let Some(role) = get("role") {
+        builder.role(role);
+    }
+    if let Some(warehouse) = get("warehouse") {
+        builder.warehouse(warehouse);
+    }
+    if let Some(database) = get("database") {
+        builder.database(database);
+    }
+    if let Some(schema) = get("schema") {
+        builder.schema(schema);
+    }
+
+    if let Some(host) = get("host") {
+        let protocol = get("protocol").unwrap_or("https");
+        let host = match connection.get("port").and_then(Value::as_integer) {
+            Some(port) => format!("{protocol}://{host}:{port}"),
+            None => format!("{protocol}://{host}"),
+        };
+        builder.host(host);
+    }
+
+    // Same table the Python connector accepts, e.g. `[dev.session_parameters]`
+    if let Some(params) = connection
+        .get("session_parameters")
+        .and_then(Value::as_table)
+    {
+        for (key, value) in params {
+            let value = match value {
+                Value::String(value) => serde_json::Value::from(value.as_str()),
+                Value::Integer(value) => serde_json::Value::from(*value),
+                Value::Float(value) => serde_json::Value::from(*value),
+                Value::Boolean(value) => serde_json::Value::from(*value),
+                _ => {
+                    return Err(error!(
+                        "session parameters must be strings, numbers or booleans",
+                        key
+                    ));
+                }
+            };
+            builder.session_parameter(key, value);
+        }
+    }
+
+    builder.strategy(strategy_from_table(connection)?);
+
+    Ok(builder)
+}
+
+fn strategy_from_table(connection: &Table) -> Result<AuthStrategy, SnowflakeError> {
+    let get = |key: &str| connection.get(key).and_then(Value::as_str);
+
+    let password = || get("password").ok_or(error!("connection is missing password"));
+    let token = || -> Result<String, SnowflakeError> {
+        match (get("token"), get("token_file_path")) {
+            (Some(token), _) => Ok(token.to_string()),
+            (None, Some(path)) => Ok(this_errors!(
+                "failed to read token_file_path",
+                std::fs::read_to_string(path)
+            )
+            .trim()
+            .to_string()),
+            (None, None) => Err(error!("connection is missing token")),
+        }
+    };
+
+    let authenticator = get("authenticator").unwrap_or("snowflake");
+
+    match authenticator.to_lowercase().as_str() {
+        "snowflake" => Ok(AuthStrategy::Password(password()?.into())),
+        "username_password_mfa" => Ok(AuthStrategy::PasswordMfa(PasswordMfa::new(password()?))),
+        "externalbrowser" => Ok(AuthStrategy::ExternalBrowser(ExternalBrowser::new())),
+        "oauth" => Ok(AuthStrategy::OAuth(
+            OAuthTokenProvider::from_token(token()?),
+        )),
+        "programmatic_access_token" => Ok(AuthStrategy::ProgrammaticAccessToken(token()?.into())),
+        #[cfg(feature = "auth-cert")]
+        "snowflake_jwt" => {
+            let path = get("private_key_file")
+                .or(get("private_key_path"))
+                .ok_or(error!("connection is missing private_key_file"))?;
+
+            let mut key = crate::auth::PrivateKey::from_file(path);
+            if let Some(passphrase) = get("private_key_file_pwd") {
+                key = key.with_passphrase(passphrase);
+            }
+
+            Ok(AuthStrategy::Certificate(key.into()))
+        }
+        #[cfg(not(feature = "auth-cert"))]
+        "snowflake_jwt" => Err(error!(
+            "snowflake_jwt connections need the auth-cert feature"
+        )),
+        okta if okta.starts_with("https://") => {
+            Ok(AuthStrategy::Okta(Okta::new(authenticator, password()?)))
+        }
+        _ => Err(error!(
+            "unsupported authenticator in connections.toml",
+            authenticator
+        )),
+    }
+}
+
+/// `SNOWFLAKE_HOME`, then `~/.snowflake` if it exists, then the platform's config directory.
+fn snowflake_home() -> Option<PathBuf> {
+    if let Some(home) = std::env::var_os(SNOWFLAKE_HOME_ENV) {
+        return Some(PathBuf::from(home));
+    }
+
+    let home = std::env::home_dir()?;
+    let dot_snowflake = home.join(".snowflake");
+    if dot_snowflake.exists() {
+        return Some(dot_snowflake);
+    }
+
+    if cfg!(target_os = "windows") {
+        Some(PathBuf::from(std::env::var_os("LOCALAPPDATA")?).join("snowflake"))
+    } else if cfg!(target_os = "macos") {
+        Some(home.join("Library/Application Support/snowflake"))
+    } else if let Some(xdg) = std::env::var_os("XDG_CONFIG_HOME") {
+        Some(PathBuf::from(xdg).join("snowflake"))
+    } else {
+        Some(home.join(".config/snowflake"))
+    }
+}
+
+fn read_toml(path: &Path) -> Result<Option<Table>, SnowflakeError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    check_permissions(path)?;
+
+    let contents = this_errors!("failed to read config file", std::fs::read_to_string(path));
+    Ok(Some(this_errors!(
+        "failed to parse config file",
+        contents.parse::<Table>()
+    )))
+}
+
+// Like the official connectors, refuse files others could have tampered with, and warn about
+// files others could read credentials from
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<(), SnowflakeError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let metadata = this_errors!(
+        "failed to read config file permissions",
+        std::fs::metadata(path)
+    );
+    let mode = metadata.permissions().mode();
+
+    if mode & 0o022 != 0 {
+        return Err(error!(
+            "config file is writable by other users, restrict it to the owner (chmod 600)",
+            path
+        ));
+    }
+
+    let skip_read_warning =
+        std::env::var(SKIP_READ_PERMISSIONS_ENV).is_ok_and(|v| v.eq_ignore_ascii_case("true"));
+    if mode & 0o044 != 0 && !skip_read_warning {
+        log::warn!(
+            "config file {} is readable by other users, restrict it to the owner (chmod 600) \
+             or set {SKIP_READ_PERMISSIONS_ENV}=true to silence this",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<(), SnowflakeError> {
+    Ok(())
+}
@@ -0,0 +1,182 @@
+#[cfg(test)]
+use super::*;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "snowflakedb-rs-{name}-{}",
+        uuid::Uuid::new_v4().simple()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_file(path: &Path, contents: &str, mode: u32) {
+    std::fs::write(path, contents).unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+}
+
+#[test]
+fn test_reads_named_connection() {
+    let dir = temp_dir("connections");
+    write_file(
+        &dir.join("connections.toml"),
+        r#"
+[dev]
+account = "myorg-dev"
+user = "alice"
+password = "hunter2"
+role = "ANALYST"
+warehouse = "DEV_WH"
+database = "DEV_DB"
+schema = "PUBLIC"
+host = "127.0.0.1"
+protocol = "http"
+port = 8080
+
+[dev.session_parameters]
+QUERY_TAG = "dev"
+statement_timeout_in_seconds = 60
+"#,
+        0o600,
+    );
+
+    let opts = SnowflakeConnectionOptsBuilder::from_connections_toml_in(&dir, Some("dev"))
+        .unwrap()
+        .pool_size(1)
+        .build()
+        .unwrap();
+
+    assert_eq!(opts.account_id, "myorg-dev");
+    assert_eq!(opts.username, "alice");
+    assert_eq!(opts.role.as_deref(), Some("ANALYST"));
+    assert_eq!(opts.warehouse.as_deref(), Some("DEV_WH"));
+    assert_eq!(opts.database.as_deref(), Some("DEV_DB"));
+    assert_eq!(opts.schema.as_deref(), Some("PUBLIC"));
+    assert_eq!(opts.host.as_deref(), Some("http://127.0.0.1:8080"));
+    assert!(matches!(&opts.strategy, AuthStrategy::Password(p) if p.expose() == "hunter2"));
+    assert_eq!(
+        opts.session_parameters.get("STATEMENT_TIMEOUT_IN_SECONDS"),
+        Some(&serde_json::json!(60))
+    );
+}
+
+#[test]
+fn test_default_connection_from_config_toml() {
+    let dir = temp_dir("config");
+    write_file(
+        &dir.join("config.toml"),
+        r#"
+default_connection_name = "prod"
+
+[connections.prod]
+account = "myorg-prod"
+user = "etl"
+authenticator = "PROGRAMMATIC_ACCESS_TOKEN"
+token = "pat-secret"
+
+[connections.other]
+account = "myorg-other"
+user = "etl"
+authenticator = "externalbrowser"
+"#,
+        0o600,
+    );
+
+    // Skip if the environment picks a different default
+    if std::env::var_os(DEFAULT_CONNECTION_NAME_ENV).is_some() {
+        return;
+    }
+
+    let opts = SnowflakeConnectionOptsBuilder::from_connections_toml_in(&dir, None)
+        .unwrap()
+        .pool_size(1)
+        .build()
+        .unwrap();
+
+    assert_eq!(opts.account_id, "myorg-prod");
+    assert!(matches!(
+        opts.strategy,
+        AuthStrategy::ProgrammaticAccessToken(_)
+    ));
+}
+
+#[test]
+fn test_connections_toml_overrides_config_toml() {
+    let dir = temp_dir("override");
+    write_file(
+        &dir.join("config.toml"),
+        "[connections.default]\naccount = \"from-config\"\npassword = \"a\"\n",
+        0o600,
+    );
+    write_file(
+        &dir.join("connections.toml"),
+        "[default]\naccount = \"from-connections\"\npassword = \"b\"\n",
+        0o600,
+    );
+
+    let opts = SnowflakeConnectionOptsBuilder::from_connections_toml_in(&dir, Some("default"))
+        .unwrap()
+        .username("alice")
+        .pool_size(1)
+        .build()
+        .unwrap();
+
+    assert_eq!(opts.account_id, "from-connections");
+}
+
+#[test]
+fn test_missing_connection() {
+    let dir = temp_dir("missing");
+    write_file(
+        &dir.join("connections.toml"),
+        "[dev]\naccount = \"a\"\n",
+        0o600,
+    );
+
+    let err = SnowflakeConnectionOptsBuilder::from_connections_toml_in(&dir, Some("prod"))
+        .err()
+        .expect("unknown connections should fail");
+
+    assert!(err.to_string().contains("connection not found"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_rejects_writable_config() {
+    let dir = temp_dir("writable");
+    write_file(
+        &dir.join("connections.toml"),
+        "[dev]\naccount = \"a\"\npassword = \"b\"\n",
+        0o622,
+    );
+
+    let err = SnowflakeConnectionOptsBuilder::from_connections_toml_in(&dir, Some("dev"))
+        .err()
+        .expect("a config writable by others should be rejected");
+
+    assert!(err.to_string().contains("writable by other users"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_reads_world_readable_config() {
+    let dir = temp_dir("readable");
+    write_file(
+        &dir.join("connections.toml"),
+        "[dev]\naccount = \"a\"\nuser = \"u\"\npassword = \"b\"\n",
+        0o644,
+    );
+
+    let opts = SnowflakeConnectionOptsBuilder::from_connections_toml_in(&dir, Some("dev"))
+        .expect("a config readable by others is only warned about")
+        .pool_size(1)
+        .build()
+        .unwrap();
+
+    assert_eq!(opts.account_id, "a");
+}
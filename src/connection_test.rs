@@ -0,0 +1,828 @@
+use std::time::Duration;
+
+#[cfg(test)]
+use super::*;
+use crate::test_utils::{
+    MockResponse, MockServer, TestClient, TestRuntime, login_success, login_success_with,
+    query_success, query_success_with, session_error, test_opts, test_opts_builder,
+};
+
+fn mock_snowflake() -> MockServer {
+    MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        "/session/heartbeat" => MockResponse::json(serde_json::json!({
+            "data": null,
+            "code": null,
+            "message": null,
+            "success": true
+        })),
+        _ => MockResponse::status(404),
+    })
+}
+
+#[tokio::test]
+async fn test_keep_alive_sends_heartbeats() {
+    let snowflake = mock_snowflake();
+
+    let mut builder = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()));
+    builder
+        .client_session_keep_alive(true)
+        .client_session_keep_alive_heartbeat_frequency(Duration::from_millis(20))
+        .runtime(TestRuntime);
+
+    let pool = builder
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    for _ in 0..100 {
+        if snowflake.requests_to("/session/heartbeat").len() >= 2 {
+            break;
+        }
+        TestRuntime.sleep(Duration::from_millis(10)).await;
+    }
+
+    let heartbeats = snowflake.requests_to("/session/heartbeat");
+    assert!(
+        heartbeats.len() >= 2,
+        "expected heartbeats, got {heartbeats:?}"
+    );
+    assert_eq!(
+        heartbeats[0].header("Authorization"),
+        Some("Snowflake Token=\"session-token\"")
+    );
+
+    // The session went back into the pool after its heartbeat
+    assert!(pool.get().await.is_ok());
+
+    drop(pool);
+    TestRuntime.sleep(Duration::from_millis(50)).await;
+    let sent = snowflake.requests_to("/session/heartbeat").len();
+    TestRuntime.sleep(Duration::from_millis(50)).await;
+    assert_eq!(snowflake.requests_to("/session/heartbeat").len(), sent);
+}
+
+#[tokio::test]
+async fn test_keep_alive_defaults_to_health_check_interval() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => {
+            login_success_with(serde_json::json!({ "healthCheckInterval": 1 }))
+        }
+        "/session/heartbeat" => MockResponse::json(serde_json::json!({
+            "data": null,
+            "code": null,
+            "message": null,
+            "success": true
+        })),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .client_session_keep_alive(true)
+        .runtime(TestRuntime)
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    TestRuntime.sleep(Duration::from_millis(500)).await;
+    assert!(snowflake.requests_to("/session/heartbeat").is_empty());
+
+    for _ in 0..100 {
+        if !snowflake.requests_to("/session/heartbeat").is_empty() {
+            break;
+        }
+        TestRuntime.sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(snowflake.requests_to("/session/heartbeat").len(), 1);
+
+    drop(pool);
+}
+
+#[cfg(not(feature = "tokio"))]
+#[tokio::test]
+async fn test_keep_alive_needs_runtime() {
+    let snowflake = mock_snowflake();
+
+    let err = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .client_session_keep_alive(true)
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .err()
+        .expect("keep alive without a runtime should fail");
+
+    assert!(err.to_string().contains("needs a runtime"));
+    assert!(snowflake.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_expired_session_is_renewed_and_retried() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        "/session/token-request" => MockResponse::json(serde_json::json!({
+            "data": {
+                "sessionId": "1234",
+                "validityInSecondsST": 3600,
+                "sessionToken": "renewed-token",
+                "validityInSecondsMT": 14400,
+                "masterToken": "renewed-master-token"
+            },
+            "code": null,
+            "message": null,
+            "success": true
+        })),
+        "/queries/v1/query-request" => match req.header("Authorization") {
+            Some("Snowflake Token=\"renewed-token\"") => query_success(),
+            _ => session_error("390112"),
+        },
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+    conn.ping()
+        .await
+        .expect("query should succeed after renewing the session");
+
+    let queries = snowflake.requests_to("/queries/v1/query-request");
+    assert_eq!(queries.len(), 2);
+    assert_eq!(
+        queries[0].query_param("requestId"),
+        queries[1].query_param("requestId")
+    );
+    assert_eq!(snowflake.requests_to("/session/token-request").len(), 1);
+}
+
+#[tokio::test]
+async fn test_master_token_expiry_logs_in_again() {
+    let logins = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let snowflake = MockServer::start({
+        let logins = logins.clone();
+        move |req| match req.path.as_str() {
+            "/session/v1/login-request" => {
+                let login = logins.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                login_success_with(serde_json::json!({ "token": format!("session-{login}") }))
+            }
+            "/queries/v1/query-request" => match req.header("Authorization") {
+                Some("Snowflake Token=\"session-1\"") => query_success(),
+                _ => session_error("390114"),
+            },
+            _ => MockResponse::status(404),
+        }
+    });
+
+    let pool = test_opts(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+    conn.ping()
+        .await
+        .expect("query should succeed after logging in again");
+
+    assert_eq!(snowflake.requests_to("/session/v1/login-request").len(), 2);
+    assert_eq!(snowflake.requests_to("/queries/v1/query-request").len(), 2);
+}
+
+#[tokio::test]
+async fn test_relogin_restores_the_session_context() {
+    let logins = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let snowflake = MockServer::start({
+        let logins = logins.clone();
+        move |req| match req.path.as_str() {
+            "/session/v1/login-request" => {
+                let login = logins.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                login_success_with(serde_json::json!({ "token": format!("session-{login}") }))
+            }
+            "/queries/v1/query-request" => match req.json()["sqlText"].as_str() {
+                Some("USE ROLE \"analyst role\"") => query_success_with(serde_json::json!({
+                    "finalDatabaseName": "OTHER_DB",
+                    "finalSchemaName": "STAGING",
+                    "finalWarehouseName": "TEST_WH",
+                    "finalRoleName": "analyst role"
+                })),
+                _ => match req.header("Authorization") {
+                    Some("Snowflake Token=\"session-1\"") => query_success(),
+                    _ => session_error("390114"),
+                },
+            },
+            _ => MockResponse::status(404),
+        }
+    });
+
+    let pool = test_opts(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+    conn.execute("USE ROLE \"analyst role\"").await.unwrap();
+    conn.ping()
+        .await
+        .expect("query should succeed after logging in again");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 2);
+    assert_eq!(logins[0].query_param("databaseName"), None);
+    assert_eq!(logins[1].query_param("databaseName"), Some("OTHER_DB"));
+    assert_eq!(logins[1].query_param("schemaName"), Some("STAGING"));
+    assert_eq!(logins[1].query_param("warehouse"), Some("TEST_WH"));
+    assert_eq!(logins[1].query_param("roleName"), Some("\"analyst role\""));
+}
+
+#[tokio::test]
+async fn test_relogin_fails_inside_a_transaction() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        "/queries/v1/query-request" => match req.json()["sqlText"].as_str() {
+            Some(sql) if sql.starts_with("INSERT") => session_error("390114"),
+            _ => query_success(),
+        },
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut tx = pool.begin().await.unwrap();
+    let Err(err) = tx.execute("INSERT INTO T VALUES (1)").await else {
+        panic!("the insert mustn't run in a new session, outside the transaction");
+    };
+    assert!(err.to_string().contains("transaction"), "{err}");
+
+    // One login for the pool and one for the transaction, none to replace it
+    assert_eq!(snowflake.requests_to("/session/v1/login-request").len(), 2);
+    let inserts = snowflake
+        .requests_to("/queries/v1/query-request")
+        .into_iter()
+        .filter(|req| req.json()["sqlText"] == "INSERT INTO T VALUES (1)")
+        .count();
+    assert_eq!(inserts, 1);
+
+    tx.rollback()
+        .await
+        .expect("rolling back a lost transaction is fine");
+}
+
+#[tokio::test]
+async fn test_session_parameters_are_sent_on_every_login() {
+    let logins = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let snowflake = MockServer::start({
+        let logins = logins.clone();
+        move |req| match req.path.as_str() {
+            "/session/v1/login-request" => {
+                let login = logins.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                login_success_with(serde_json::json!({ "token": format!("session-{login}") }))
+            }
+            "/queries/v1/query-request" => match req.header("Authorization") {
+                Some("Snowflake Token=\"session-1\"") => query_success(),
+                _ => session_error("390114"),
+            },
+            _ => MockResponse::status(404),
+        }
+    });
+
+    let pool = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .session_parameter("query_tag", "nightly-etl")
+        .session_parameters([("TIMEZONE", "UTC")])
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+    conn.ping()
+        .await
+        .expect("query should succeed after logging in again");
+
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 2);
+    for login in logins {
+        let params = &login.json()["data"]["SESSION_PARAMETERS"];
+        assert_eq!(params["QUERY_TAG"], "nightly-etl");
+        assert_eq!(params["TIMEZONE"], "UTC");
+        assert_eq!(params["CLIENT_VALIDATE_DEFAULT_PARAMETERS"], true);
+    }
+}
+
+#[tokio::test]
+async fn test_client_identity_and_result_format() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        "/queries/v1/query-request" => query_success(),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .client_app_id("JDBC")
+        .client_app_version("3.24.0")
+        .application("nightly-etl")
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+    conn.ping().await.expect("query should succeed");
+
+    let login = &snowflake.requests_to("/session/v1/login-request")[0].json()["data"];
+    assert_eq!(login["CLIENT_APP_ID"], "JDBC");
+    assert_eq!(login["CLIENT_APP_VERSION"], "3.24.0");
+    assert_eq!(login["CLIENT_ENVIRONMENT"]["APPLICATION"], "nightly-etl");
+
+    let query = snowflake.requests_to("/queries/v1/query-request")[0].json();
+    assert_eq!(query["parameters"]["JDBC_QUERY_RESULT_FORMAT"], "JSON");
+}
+
+#[tokio::test]
+async fn test_custom_client_app_id_uses_the_go_result_format() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        "/queries/v1/query-request" => query_success(),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .client_app_id("nightly-etl")
+        .client_app_version("2.0.0")
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+    conn.ping().await.expect("query should succeed");
+
+    let login = &snowflake.requests_to("/session/v1/login-request")[0].json()["data"];
+    assert_eq!(login["CLIENT_APP_ID"], "nightly-etl");
+
+    let query = &snowflake.requests_to("/queries/v1/query-request")[0];
+    assert_eq!(query.header("ACCEPT"), Some("application/json"));
+    assert_eq!(
+        query.json()["parameters"],
+        serde_json::json!({ "GO_QUERY_RESULT_FORMAT": "JSON" })
+    );
+}
+
+#[tokio::test]
+async fn test_session_info_follows_use_statements() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success_with(serde_json::json!({
+            "parameters": [{ "name": "TIMEZONE", "value": "UTC" }],
+            "sessionInfo": {
+                "databaseName": "TEST_DB",
+                "schemaName": "PUBLIC",
+                "warehouseName": null,
+                "roleName": "PUBLIC"
+            }
+        })),
+        "/queries/v1/query-request" => match req.json()["sqlText"].as_str() {
+            Some("USE ROLE ANALYST") => query_success_with(serde_json::json!({
+                "finalDatabaseName": "TEST_DB",
+                "finalSchemaName": "PUBLIC",
+                "finalWarehouseName": "",
+                "finalRoleName": "ANALYST",
+                "parameters": [{ "name": "QUERY_TAG", "value": "audit" }]
+            })),
+            _ => query_success(),
+        },
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+
+    let info = conn.session_info().await.unwrap();
+    assert_eq!(info.session_id, 1234);
+    assert_eq!(info.server_version, "9.0.0");
+    assert_eq!(info.display_user_name, "TEST_USER");
+    assert_eq!(info.role.as_deref(), Some("PUBLIC"));
+    assert_eq!(info.warehouse, None);
+    assert_eq!(info.parameters["TIMEZONE"], "UTC");
+
+    conn.execute("USE ROLE ANALYST").await.unwrap();
+
+    let info = conn.session_info().await.unwrap();
+    assert_eq!(info.role.as_deref(), Some("ANALYST"));
+    assert_eq!(info.database.as_deref(), Some("TEST_DB"));
+    assert_eq!(info.warehouse, None);
+    assert_eq!(info.parameters["TIMEZONE"], "UTC");
+    assert_eq!(info.parameters["QUERY_TAG"], "audit");
+
+    // Responses without the final context leave it as it was
+    conn.ping().await.unwrap();
+    assert_eq!(
+        conn.session_info().await.unwrap().role.as_deref(),
+        Some("ANALYST")
+    );
+}
+
+#[tokio::test]
+async fn test_get_waits_for_a_session_in_order() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        "/queries/v1/query-request" => query_success(),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let first = pool.get().await.unwrap();
+    let Err(err) = pool.try_get().await else {
+        panic!("try_get shouldn't wait for the session in use");
+    };
+    assert!(err.to_string().contains("no available sessions"));
+
+    let order = std::sync::Mutex::new(Vec::new());
+    futures_util::join!(
+        async {
+            let _conn = pool.get().await.unwrap();
+            order.lock().unwrap().push("second");
+        },
+        async {
+            let _conn = pool.get().await.unwrap();
+            order.lock().unwrap().push("third");
+        },
+        async {
+            TestRuntime.sleep(Duration::from_millis(20)).await;
+            drop(first);
+        },
+    );
+
+    assert_eq!(*order.lock().unwrap(), ["second", "third"]);
+    assert!(pool.try_get().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_get_times_out_without_losing_the_session() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        "/queries/v1/query-request" => query_success(),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .acquire_timeout(Duration::from_millis(20))
+        .runtime(TestRuntime)
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let conn = pool.get().await.unwrap();
+    let Err(err) = pool.get().await else {
+        panic!("get should give up after acquire_timeout");
+    };
+    assert!(err.to_string().contains("timed out"));
+
+    drop(conn);
+    assert!(pool.get().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_timed_out_waiters_leave_the_queue() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .acquire_timeout(Duration::from_millis(10))
+        .runtime(TestRuntime)
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let _conn = pool.get().await.unwrap();
+    for _ in 0..3 {
+        assert!(pool.get().await.is_err());
+    }
+
+    assert_eq!(pool.pool.lock().unwrap().waiters.len(), 0);
+}
+
+#[tokio::test]
+async fn test_waiters_fail_once_every_session_is_lost() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let conn = pool.get().await.unwrap();
+    // Like a query still running when its connection is dropped
+    let in_flight = conn.session.clone().unwrap();
+
+    let (waiting, _) = futures_util::join!(pool.get(), async {
+        TestRuntime.sleep(Duration::from_millis(20)).await;
+        drop(conn);
+    });
+    let Err(err) = waiting else {
+        panic!("get should fail rather than wait for a session that never comes back");
+    };
+    assert!(err.to_string().contains("no sessions left"));
+
+    drop(in_flight);
+    let Err(err) = pool.get().await else {
+        panic!("get should fail right away once the pool is empty");
+    };
+    assert!(err.to_string().contains("no sessions left"));
+}
+
+#[tokio::test]
+async fn test_connect_with_prebuilt_client() {
+    let snowflake = mock_snowflake();
+
+    let pool = test_opts(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .connect_json_using(TestClient)
+        .await
+        .expect("failed to connect");
+
+    assert!(pool.get().await.is_ok());
+    assert_eq!(snowflake.requests_to("/session/v1/login-request").len(), 1);
+}
+
+#[cfg(feature = "reqwest")]
+#[tokio::test]
+async fn test_reqwest_client_uses_proxy_and_user_agent() {
+    // Requests through a plain http proxy are sent to it with the full url as their target
+    let proxy = MockServer::start(|req| match req.path.as_str() {
+        "http://snowflake.invalid/session/v1/login-request" => login_success(),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts_builder(
+        "http://snowflake.invalid",
+        AuthStrategy::Password("hunter2".into()),
+    )
+    .proxy(proxy.url())
+    .user_agent("etl-runner/1.0")
+    .connect_timeout(Duration::from_secs(5))
+    .build()
+    .unwrap()
+    .connect_json()
+    .await
+    .expect("failed to connect through the proxy");
+
+    assert!(pool.get().await.is_ok());
+
+    let logins = proxy.requests_to("http://snowflake.invalid/session/v1/login-request");
+    assert_eq!(logins.len(), 1);
+    assert_eq!(logins[0].header("User-Agent"), Some("etl-runner/1.0"));
+}
+
+#[cfg(feature = "reqwest")]
+#[test]
+fn test_reqwest_client_rejects_invalid_root_certificate() {
+    let opts = test_opts_builder(
+        "http://snowflake.invalid",
+        AuthStrategy::Password("hunter2".into()),
+    )
+    .root_certificate("not a certificate")
+    .build()
+    .unwrap();
+
+    let err = <reqwest::Client as SnowflakeHttpClient>::from_config(&opts.http_client_config())
+        .expect_err("an invalid certificate should fail");
+
+    assert!(err.to_string().contains("invalid root certificate"));
+}
+
+fn fast_retries() -> crate::RetryPolicy {
+    crate::RetryPolicy::new().initial_backoff(Duration::from_millis(1))
+}
+
+#[tokio::test]
+async fn test_query_is_retried_with_the_same_request_id() {
+    let queries = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let snowflake = MockServer::start({
+        let queries = queries.clone();
+        move |req| match req.path.as_str() {
+            "/session/v1/login-request" => login_success(),
+            "/queries/v1/query-request" => {
+                match queries.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                    0 => MockResponse::status(503),
+                    _ => query_success(),
+                }
+            }
+            _ => MockResponse::status(404),
+        }
+    });
+
+    let pool = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .retry_policy(fast_retries())
+        .runtime(TestRuntime)
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+    conn.ping()
+        .await
+        .expect("query should succeed after a retry");
+
+    let queries = snowflake.requests_to("/queries/v1/query-request");
+    assert_eq!(queries.len(), 2);
+    assert_eq!(
+        queries[0].query_param("requestId"),
+        queries[1].query_param("requestId")
+    );
+    assert_eq!(queries[0].query_param("retryCount"), None);
+    assert_eq!(queries[1].query_param("retryCount"), Some("1"));
+    assert_eq!(queries[1].query_param("retryReason"), Some("503"));
+}
+
+#[tokio::test]
+async fn test_login_gives_up_after_max_attempts() {
+    let snowflake = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => {
+            let mut resp = MockResponse::status(429);
+            resp.headers.push(("Retry-After".into(), "0".into()));
+            resp
+        }
+        _ => MockResponse::status(404),
+    });
+
+    let err = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .retry_policy(fast_retries().max_attempts(3))
+        .runtime(TestRuntime)
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .err()
+        .expect("login should fail once retries run out");
+
+    assert!(err.to_string().contains("retryable status"));
+    let logins = snowflake.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 3);
+    assert_eq!(logins[2].query_param("retryReason"), Some("429"));
+}
+
+/// A client with only `get` and `post`, which fail for error statuses.
+#[derive(Clone)]
+struct GetPostClient;
+
+impl crate::SnowflakeHttpClient for GetPostClient {
+    fn new() -> Self {
+        GetPostClient
+    }
+
+    async fn get(
+        &self,
+        url: &str,
+        headers: std::collections::HashMap<String, String>,
+    ) -> Result<Vec<u8>, crate::SnowflakeError> {
+        Self::check(crate::test_utils::send_request("GET", url, &headers, &[])?)
+    }
+
+    async fn post(
+        &self,
+        url: &str,
+        body: Vec<u8>,
+        headers: std::collections::HashMap<String, String>,
+    ) -> Result<Vec<u8>, crate::SnowflakeError> {
+        Self::check(crate::test_utils::send_request(
+            "POST", url, &headers, &body,
+        )?)
+    }
+}
+
+impl GetPostClient {
+    fn check((status, body): (u16, Vec<u8>)) -> Result<Vec<u8>, crate::SnowflakeError> {
+        match status {
+            200..=299 => Ok(body),
+            _ => Err(crate::error!("unexpected status", status)),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_default_send_retries_client_errors() {
+    let queries = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let snowflake = MockServer::start({
+        let queries = queries.clone();
+        move |req| match req.path.as_str() {
+            "/session/v1/login-request" => login_success(),
+            "/queries/v1/query-request" => {
+                match queries.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                    0 => MockResponse::status(503),
+                    _ => query_success(),
+                }
+            }
+            _ => MockResponse::status(404),
+        }
+    });
+
+    let pool = test_opts_builder(&snowflake.url(), AuthStrategy::Password("hunter2".into()))
+        .retry_policy(fast_retries())
+        .runtime(TestRuntime)
+        .build()
+        .unwrap()
+        .connect_json_with_client::<GetPostClient>()
+        .await
+        .expect("failed to connect");
+
+    let mut conn = pool.get().await.unwrap();
+    conn.ping()
+        .await
+        .expect("query should succeed after a retry");
+
+    // The status is unknown, so the retry is reported like a connection error
+    let queries = snowflake.requests_to("/queries/v1/query-request");
+    assert_eq!(queries.len(), 2);
+    assert_eq!(queries[1].query_param("retryCount"), Some("1"));
+    assert_eq!(queries[1].query_param("retryReason"), Some("0"));
+}
+
+#[tokio::test]
+async fn test_fails_over_to_the_next_host_and_sticks_to_it() {
+    // Nothing listens on a port that was just released, so connecting to it fails
+    let unreachable = {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    };
+
+    let secondary = MockServer::start(|req| match req.path.as_str() {
+        "/session/v1/login-request" => login_success(),
+        "/queries/v1/query-request" => query_success(),
+        _ => MockResponse::status(404),
+    });
+
+    let pool = test_opts_builder(&unreachable, AuthStrategy::Password("hunter2".into()))
+        .failover_host(secondary.url())
+        .retry_policy(crate::RetryPolicy::none())
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("login should fail over to the secondary host");
+
+    let logins = secondary.requests_to("/session/v1/login-request");
+    assert_eq!(logins.len(), 1);
+    assert_eq!(logins[0].query_param("retryCount"), Some("1"));
+    assert_eq!(logins[0].query_param("retryReason"), Some("0"));
+
+    let mut conn = pool.get().await.unwrap();
+    conn.ping()
+        .await
+        .expect("query should go to the secondary host");
+
+    // Later requests go straight to the host that worked
+    let queries = secondary.requests_to("/queries/v1/query-request");
+    assert_eq!(queries.len(), 1);
+    assert_eq!(queries[0].query_param("retryCount"), None);
+}
+
+#[tokio::test]
+async fn test_fails_over_on_service_unavailable() {
+    let primary = MockServer::start(|_| MockResponse::status(503));
+    let secondary = mock_snowflake();
+
+    test_opts_builder(&primary.url(), AuthStrategy::Password("hunter2".into()))
+        .failover_host(secondary.url())
+        .build()
+        .unwrap()
+        .connect_json_with_client::<TestClient>()
+        .await
+        .expect("login should fail over to the secondary host");
+
+    assert_eq!(primary.requests_to("/session/v1/login-request").len(), 1);
+    assert_eq!(secondary.requests_to("/session/v1/login-request").len(), 1);
+}
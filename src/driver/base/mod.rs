@@ -5,7 +5,7 @@ use serde_json::json;
 
 use crate::{
     SnowflakeError,
-    auth::session::Session,
+    auth::session::{Session, TransactionStatement},
     error,
     http::{self, client::SnowflakeHttpClient},
     this_errors,
@@ -16,12 +16,52 @@ pub(crate) mod response;
 
 static QUERY_REQUEST_PATH: &'static str = "/queries/v1/query-request";
 
+/// The rowset format a protocol reads, asked for explicitly on every query so it doesn't depend
+/// on the format Snowflake defaults to for `client_app_id`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ResultFormat {
+    Json,
+    #[cfg(feature = "arrow")]
+    Arrow,
+}
+
+impl ResultFormat {
+    fn accept_header(self) -> &'static str {
+        match self {
+            ResultFormat::Json => "application/json",
+            #[cfg(feature = "arrow")]
+            ResultFormat::Arrow => "application/snowflake",
+        }
+    }
+
+    fn parameter_value(self) -> &'static str {
+        match self {
+            ResultFormat::Json => "JSON",
+            #[cfg(feature = "arrow")]
+            ResultFormat::Arrow => "ARROW",
+        }
+    }
+}
+
+/// The session parameter Snowflake reads the result format from, which is specific to each client.
+pub(crate) fn result_format_parameter(client_app_id: &str) -> &'static str {
+    match client_app_id {
+        "Go" => "GO_QUERY_RESULT_FORMAT",
+        "JDBC" => "JDBC_QUERY_RESULT_FORMAT",
+        "ODBC" => "ODBC_QUERY_RESULT_FORMAT",
+        "PythonConnector" => "PYTHON_CONNECTOR_QUERY_RESULT_FORMAT",
+        "libsnowflakeclient" => "C_API_QUERY_RESULT_FORMAT",
+        // Other ids have no parameter of their own, so use the default identity's
+        _ => "GO_QUERY_RESULT_FORMAT",
+    }
+}
+
 #[derive(Builder)]
 pub(crate) struct BinaryQuery {
     pub sql_text: String,
 
-    #[builder(setter(into), default = "\"application/json\".into()")]
-    pub accept_header: String,
+    #[builder(default = "ResultFormat::Json")]
+    pub result_format: ResultFormat,
 
     #[builder(setter(into, strip_option), default = true)]
     pub is_describe_only: bool,
@@ -36,7 +76,10 @@ impl BinaryQuery {
         session: &mut Session<C>,
     ) -> Result<response::RawQueryResponse, SnowflakeError> {
         let mut headers = HashMap::new();
-        headers.insert("ACCEPT".to_string(), self.accept_header.clone());
+        headers.insert(
+            "ACCEPT".to_string(),
+            self.result_format.accept_header().to_string(),
+        );
 
         let bindings = self
             .bindings
@@ -46,6 +89,9 @@ impl BinaryQuery {
 
         // let bindings = self.convert_bindings();
 
+        let format_parameter =
+            result_format_parameter(&session.get_conn().get_opts().client_app_id);
+
         let body = json!({
             "sqlText": self.sql_text,
             "sequenceId": session.get_sequence_count(),
@@ -53,22 +99,60 @@ impl BinaryQuery {
             "bindings": bindings,
             "asyncExec": false,
             "isInternal": false,
+            "parameters": {
+                format_parameter: self.result_format.parameter_value(),
+            },
         });
 
-        let conn = session.get_conn();
-        let token = session.get_token().await?;
-        let request = this_errors!(
-            "failed to send query request",
-            http::RequestBuilder::default()
-                .path(QUERY_REQUEST_PATH)
-                .connection(conn)
-                .headers(headers.clone())
-                .auth_token(token)
-                .build()
-        );
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let mut retried = false;
+
+        let statement = match self.is_describe_only {
+            true => None,
+            false => TransactionStatement::parse(&self.sql_text),
+        };
+        // Whether it succeeds or the server session is already gone, no transaction is left open,
+        // so a ROLLBACK may log in again
+        if statement == Some(TransactionStatement::Rollback) {
+            session.in_transaction = false;
+        }
+
+        let response = loop {
+            let conn = session.get_conn();
+            let token = session.get_token().await?;
+            let request = this_errors!(
+                "failed to send query request",
+                http::RequestBuilder::default()
+                    .path(QUERY_REQUEST_PATH)
+                    .connection(conn)
+                    .headers(headers.clone())
+                    .auth_token(token)
+                    .request_id(request_id.as_str())
+                    .retry(true)
+                    .build()
+            );
+
+            let response = request.post::<response::ExecResponse>(body.clone()).await?;
+
+            // Snowflake expired the session early, get a new token and send the same request again
+            if let response::ExecResponse::Failure(err) = &response
+                && !retried
+                && session.recover_expired(err.code.as_deref()).await?
+            {
+                retried = true;
+                continue;
+            }
+
+            break response;
+        };
 
-        let response = request.post::<response::ExecResponse>(body).await?;
         let query_result = self.process_response(session, response).await?;
+        session.info.update(&query_result);
+        match statement {
+            Some(TransactionStatement::Begin) => session.in_transaction = true,
+            Some(TransactionStatement::Commit) => session.in_transaction = false,
+            _ => {}
+        }
 
         Ok(query_result)
     }
@@ -92,6 +176,7 @@ impl BinaryQuery {
                                 .auth_token(token)
                                 .path(async_data.get_result_url)
                                 .connection(session.get_conn())
+                                .retry(true)
                                 .build()
                         );
 
@@ -21,7 +21,7 @@ use crate::{
     connection::Connection,
     driver::{base::bindings, primitives::column::Column},
     error,
-    http::{self, GenericResponse, client::SnowflakeHttpClient},
+    http::{self, GenericResponse, NameValueParameter, client::SnowflakeHttpClient},
     this_errors,
 };
 
@@ -68,7 +68,9 @@ pub struct QueryStats {
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub(crate) struct RawQueryResponse {
-    // pub(crate) parameters: Vec<super::NameValueParameter>,
+    /// Session parameters the query changed, e.g. by running `ALTER SESSION`.
+    #[serde(default)]
+    pub(crate) parameters: Vec<NameValueParameter>,
     pub(crate) rowtype: Vec<Column>,
 
     #[serde(default)]
@@ -98,6 +100,12 @@ pub(crate) struct RawQueryResponse {
 
     // no idea what this is but looks important
     pub(crate) qrmk: Option<String>,
+
+    // The session's context once the query ran, which `USE` statements change
+    pub(crate) final_database_name: Option<String>,
+    pub(crate) final_schema_name: Option<String>,
+    pub(crate) final_warehouse_name: Option<String>,
+    pub(crate) final_role_name: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -129,7 +137,8 @@ pub struct ExecResponseErrorData {
 #[serde(untagged)]
 pub(crate) enum ExecResponse {
     Success(GenericResponse<ExecResponseData>),
-    Failure(GenericResponse<ExecResponseErrorData>),
+    // Session errors (e.g. an expired token) come without any data
+    Failure(GenericResponse<Option<ExecResponseErrorData>>),
 }
 
 impl RawQueryResponse {
@@ -174,6 +183,7 @@ impl RawQueryResponse {
                             .full_url(url)
                             .headers(headers)
                             .connection(conn)
+                            .retry(true)
                             .build()
                             .map_err(|e| error!("failed to build get chunk request", e))?;
 
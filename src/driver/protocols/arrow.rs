@@ -19,7 +19,7 @@ use crate::{
     driver::{
         Protocol,
         base::{
-            BinaryQueryBuilder,
+            BinaryQueryBuilder, ResultFormat,
             bindings::{BindMetadata, Bindings},
             response::RawQueryResponse,
         },
@@ -74,7 +74,7 @@ impl<C: SnowflakeHttpClient> Query<C> for ArrowQuery<C> {
         let query = this_errors!(
             "failed to build underlying binary query",
             BinaryQueryBuilder::default()
-                .accept_header("application/snowflake")
+                .result_format(ResultFormat::Arrow)
                 .sql_text(self.query)
                 .is_describe_only(false)
                 .bindings(self.bindings)
@@ -108,7 +108,7 @@ impl<C: SnowflakeHttpClient> Query<C> for ArrowQuery<C> {
         let query = this_errors!(
             "failed to build underlying binary query",
             BinaryQueryBuilder::default()
-                .accept_header("application/snowflake")
+                .result_format(ResultFormat::Arrow)
                 .sql_text(self.query)
                 .is_describe_only(true)
                 .bindings(self.bindings)
@@ -13,7 +13,7 @@ use crate::{
     driver::{
         Protocol,
         base::{
-            BinaryQueryBuilder,
+            BinaryQueryBuilder, ResultFormat,
             bindings::{BindMetadata, Bindings},
             response::RawQueryResponse,
         },
@@ -79,7 +79,7 @@ impl<C: SnowflakeHttpClient> Query<C> for JsonQuery<C> {
         let query = this_errors!(
             "failed to build underlying binary query",
             BinaryQueryBuilder::default()
-                .accept_header("application/json")
+                .result_format(ResultFormat::Json)
                 .sql_text(self.query)
                 .is_describe_only(true)
                 .bindings(self.bindings)
@@ -109,7 +109,7 @@ impl<C: SnowflakeHttpClient> Query<C> for JsonQuery<C> {
         let query = this_errors!(
             "failed to build underlying binary query",
             BinaryQueryBuilder::default()
-                .accept_header("application/json")
+                .result_format(ResultFormat::Json)
                 .sql_text(self.query)
                 .is_describe_only(false)
                 .bindings(self.bindings)
@@ -0,0 +1,515 @@
+//! Parses and formats connection strings (DSNs) in the format used by gosnowflake, and the ADBC
+//! driver and dbt adapters built on it, e.g. `user:password@account/database/schema?warehouse=WH`.
+
+use std::{fmt::Write, time::Duration};
+
+use crate::{
+    SnowflakeConnectionOpts, SnowflakeConnectionOptsBuilder, SnowflakeError,
+    auth::{
+        AuthStrategy, ExternalBrowser, OAuthTokenProvider, Okta, PasswordMfa,
+        session::DEFAULT_APPLICATION,
+    },
+    error,
+    http::url,
+    this_errors,
+};
+
+#[cfg(test)]
+#[path = "./dsn_test.rs"]
+mod dsn_test;
+
+static DSN_SCHEME: &str = "snowflake://";
+static REDACTED: &str = "****";
+
+// gosnowflake's own driver settings. They aren't Snowflake parameters, so they're ignored rather
+// than sent as session parameters, which would fail the login or set nonsense
+static DRIVER_ONLY_PARAMS: &[&str] = &[
+    "loginTimeout",
+    "requestTimeout",
+    "jwtTimeout",
+    "jwtClientTimeout",
+    "clientTimeout",
+    "externalBrowserTimeout",
+    "maxRetryCount",
+    "ocspFailOpen",
+    "insecureMode",
+    "disableOCSPChecks",
+    "certRevocationCheckMode",
+    "validateDefaultParameters",
+    "tracing",
+    "logQueryText",
+    "logQueryParameters",
+    "tmpDirPath",
+    "clientConfigFile",
+    "clientRequestMfaToken",
+    "clientStoreTemporaryCredential",
+    "disableQueryContextCache",
+    "includeRetryReason",
+    "disableConsoleLogin",
+    "disableSamlURLCheck",
+    "serverSessionKeepAlive",
+    "oauthAuthorizationUrl",
+    "oauthRedirectUri",
+    "enableSingleUseRefreshTokens",
+    "workloadIdentityProvider",
+    "workloadIdentityEntraResource",
+    "workloadIdentityImpersonationPath",
+];
+
+// gosnowflake leaves the default region out of account hosts
+static DEFAULT_REGION: &str = "us-west-2";
+
+static DOMAINS: [&str; 2] = [".snowflakecomputing.com", ".snowflakecomputing.cn"];
+
+impl SnowflakeConnectionOptsBuilder {
+    /// Starts a builder from a DSN in gosnowflake's format:
+    ///
+    /// `user[:password]@account[/database[/schema]][?param=value&...]`
+    ///
+    /// or, to connect to a specific host, `user[:password]@host[:port]/...?account=account`.
+    /// A `*.snowflakecomputing.com` address is a host even without an `account` parameter.
+    ///
+    /// Special characters in the user, password, database and schema need to be percent-encoded.
+    /// Supported parameters are `account`, `region`, `warehouse`, `role`, `application`, `database`,
+    /// `schema`, `host`, `port`, `protocol`, `authenticator`, `token`, `passcode`,
+    /// `passcodeInPassword`, `privateKey`, `oauthClientId`, `oauthClientSecret`,
+    /// `oauthTokenRequestUrl`, `oauthScope`, `clientSessionKeepAlive` and
+    /// `clientSessionKeepAliveHeartbeatFrequency`. Other gosnowflake settings, like `loginTimeout`
+    /// or `ocspFailOpen`, are ignored. Any other parameter is set as a session parameter,
+    /// e.g. `?QUERY_TAG=etl&TIMEZONE=UTC`.
+    /// `pool_size` defaults to 1 and can be changed on the returned builder.
+    pub fn from_dsn(dsn: &str) -> Result<Self, SnowflakeError> {
+        let dsn = dsn.strip_prefix(DSN_SCHEME).unwrap_or(dsn);
+
+        let (location, query) = dsn.split_once('?').unwrap_or((dsn, ""));
+        let (userinfo, location) = location
+            .rsplit_once('@')
+            .ok_or(error!("dsn is missing the user, expected user@account"))?;
+        let (address, path) = location.split_once('/').unwrap_or((location, ""));
+
+        let (user, password) = match userinfo.split_once(':') {
+            Some((user, password)) => (user, Some(password)),
+            None => (userinfo, None),
+        };
+        let user = this_errors!("invalid user in dsn", url::decode_component(user));
+        let password = match password {
+            Some(password) => Some(this_errors!(
+                "invalid password in dsn",
+                url::decode_component(password)
+            )),
+            None => None,
+        };
+
+        let mut path = path.split('/').filter(|segment| !segment.is_empty());
+        let mut database = match path.next() {
+            Some(database) => Some(this_errors!(
+                "invalid database in dsn",
+                url::decode_component(database)
+            )),
+            None => None,
+        };
+        let mut schema = match path.next() {
+            Some(schema) => Some(this_errors!(
+                "invalid schema in dsn",
+                url::decode_component(schema)
+            )),
+            None => None,
+        };
+        if path.next().is_some() {
+            return Err(error!(
+                "dsn path has too many segments, expected /database/schema"
+            ));
+        }
+
+        let params = this_errors!("invalid dsn parameters", url::parse_query(query));
+
+        let mut builder = SnowflakeConnectionOptsBuilder::default();
+        builder.pool_size(1);
+        if !user.is_empty() {
+            builder.username(user);
+        }
+
+        let mut account = None;
+        let mut region = None;
+        let mut host = None;
+        let mut port = None;
+        let mut protocol = None;
+        let mut auth = DsnAuth {
+            password,
+            ..Default::default()
+        };
+
+        for (key, value) in params {
+            match key.as_str() {
+                "account" => account = Some(value),
+                "region" => region = Some(value),
+                "warehouse" => {
+                    builder.warehouse(value);
+                }
+                "role" => {
+                    builder.role(value);
+                }
+                "application" => {
+                    builder.application(value);
+                }
+                "database" => database = Some(value),
+                "schema" => schema = Some(value),
+                "host" => host = Some(value),
+                "port" => port = Some(value),
+                "protocol" => protocol = Some(value),
+                "authenticator" => auth.authenticator = Some(value),
+                "token" => auth.token = Some(value),
+                "passcode" => auth.passcode = Some(value),
+                "passcodeInPassword" => auth.passcode_in_password = parse_bool(&key, &value)?,
+                "privateKey" => auth.private_key = Some(value),
+                "oauthClientId" => auth.oauth.client_id = Some(value),
+                "oauthClientSecret" => auth.oauth.client_secret = Some(value),
+                "oauthTokenRequestUrl" => auth.oauth.token_url = Some(value),
+                "oauthScope" => auth.oauth.scope = Some(value),
+                "clientSessionKeepAlive" => {
+                    builder.client_session_keep_alive(parse_bool(&key, &value)?);
+                }
+                "clientSessionKeepAliveHeartbeatFrequency" => {
+                    let seconds = this_errors!(
+                        "clientSessionKeepAliveHeartbeatFrequency must be a number of seconds",
+                        value.parse::<u64>()
+                    );
+                    builder.client_session_keep_alive_heartbeat_frequency(Duration::from_secs(
+                        seconds,
+                    ));
+                }
+                _ if DRIVER_ONLY_PARAMS.contains(&key.as_str()) => {}
+                // Like gosnowflake, anything else is a session parameter
+                _ => {
+                    builder.session_parameter(key, value);
+                }
+            }
+        }
+
+        if let Some(database) = database {
+            builder.database(database);
+        }
+        if let Some(schema) = schema {
+            builder.schema(schema);
+        }
+
+        // Like gosnowflake, an `account` parameter means the DSN addresses a host rather than an account
+        let (address, address_port) = match address.rsplit_once(':') {
+            Some((address, port)) => (address, Some(port.to_string())),
+            None => (address, None),
+        };
+        // The account is everything before the domain, e.g. `xy12345.us-east-1`
+        let hostname_account = DOMAINS.iter().find_map(|domain| {
+            let split = address.len().checked_sub(domain.len())?;
+            (address.is_char_boundary(split) && address[split..].eq_ignore_ascii_case(domain))
+                .then(|| &address[..split])
+        });
+        let account = match (account, hostname_account) {
+            (Some(account), _) => {
+                host = host.or(Some(address.to_string()));
+                account
+            }
+            (None, Some(account)) => {
+                host = host.or(Some(address.to_string()));
+                account.to_string()
+            }
+            (None, None) if address_port.is_some() => {
+                return Err(error!("dsn with a port needs an account parameter"));
+            }
+            (None, None) => address.to_string(),
+        };
+        match region {
+            Some(region) if region != DEFAULT_REGION && !account.contains('.') => {
+                builder.account_id(format!("{account}.{region}"));
+            }
+            _ => {
+                builder.account_id(account);
+            }
+        }
+
+        if let Some(host) = host {
+            let protocol = protocol.as_deref().unwrap_or("https");
+            let host = match port.or(address_port) {
+                _ if host.contains("://") => host,
+                Some(port) => format!("{protocol}://{host}:{port}"),
+                None => format!("{protocol}://{host}"),
+            };
+            builder.host(host);
+        }
+
+        builder.strategy(auth.strategy()?);
+
+        Ok(builder)
+    }
+}
+
+impl SnowflakeConnectionOpts {
+    /// Parses a DSN into connection options with a `pool_size` of 1.
+    /// See `SnowflakeConnectionOptsBuilder::from_dsn` for the format and to change the pool size.
+    pub fn from_dsn(dsn: &str) -> Result<Self, SnowflakeError> {
+        let opts = this_errors!(
+            "incomplete dsn",
+            SnowflakeConnectionOptsBuilder::from_dsn(dsn)?.build()
+        );
+        Ok(opts)
+    }
+
+    /// Formats these options as a DSN, for passing on to other tools or logging.
+    ///
+    /// Passwords, tokens, passcodes and private keys are replaced with `****`, so the DSN can't be
+    /// used to log in as is. Options that have no DSN parameter, like `pool_size`, are left out.
+    pub fn to_dsn(&self) -> String {
+        let mut dsn = url::encode(&self.username);
+        if self.has_password() {
+            dsn.push(':');
+            dsn.push_str(REDACTED);
+        }
+        dsn.push('@');
+
+        let mut params: Vec<(&str, String)> = Vec::new();
+
+        let origin = self.host.as_deref().and_then(url::origin);
+        match (&origin, &self.host) {
+            (Some((scheme, host, port)), _) => {
+                let _ = write!(dsn, "{host}:{port}");
+                params.push(("account", url::encode(&self.account_id)));
+                if scheme != "https" {
+                    params.push(("protocol", url::encode(scheme)));
+                }
+            }
+            // Not a URL, so keep it as a parameter rather than guessing at its parts
+            (None, Some(host)) => {
+                dsn.push_str(&url::encode(&self.account_id));
+                params.push(("host", url::encode(host)));
+            }
+            (None, None) => dsn.push_str(&url::encode(&self.account_id)),
+        }
+
+        match (&self.database, &self.schema) {
+            (Some(database), schema) => {
+                dsn.push('/');
+                dsn.push_str(&url::encode(database));
+                if let Some(schema) = schema {
+                    dsn.push('/');
+                    dsn.push_str(&url::encode(schema));
+                }
+            }
+            (None, Some(schema)) => params.push(("schema", url::encode(schema))),
+            (None, None) => {}
+        }
+
+        if let Some(warehouse) = &self.warehouse {
+            params.push(("warehouse", url::encode(warehouse)));
+        }
+        if let Some(role) = &self.role {
+            params.push(("role", url::encode(role)));
+        }
+        if self.application != DEFAULT_APPLICATION {
+            params.push(("application", url::encode(&self.application)));
+        }
+
+        self.push_auth_params(&mut params);
+
+        if self.client_session_keep_alive {
+            params.push(("clientSessionKeepAlive", "true".into()));
+        }
+        if let Some(frequency) = self.client_session_keep_alive_heartbeat_frequency {
+            params.push((
+                "clientSessionKeepAliveHeartbeatFrequency",
+                frequency.as_secs().to_string(),
+            ));
+        }
+
+        let mut session_parameters: Vec<_> = self.session_parameters.iter().collect();
+        session_parameters.sort_by_key(|(key, _)| *key);
+        for (key, value) in session_parameters {
+            let value = match value {
+                serde_json::Value::String(value) => url::encode(value),
+                value => url::encode(&value.to_string()),
+            };
+            params.push((key.as_str(), value));
+        }
+
+        let mut prefix = '?';
+        for (key, value) in params {
+            let _ = write!(dsn, "{prefix}{key}={value}");
+            prefix = '&';
+        }
+
+        dsn
+    }
+
+    fn has_password(&self) -> bool {
+        matches!(
+            self.strategy,
+            AuthStrategy::Password(_) | AuthStrategy::PasswordMfa(_) | AuthStrategy::Okta(_)
+        )
+    }
+
+    fn push_auth_params(&self, params: &mut Vec<(&str, String)>) {
+        // Credentials follow the authenticator, so collect them before knowing which one it is
+        let mut credentials = Vec::new();
+        let authenticator = match &self.strategy {
+            AuthStrategy::Password(_) => None,
+            AuthStrategy::PasswordMfa(mfa) => {
+                if mfa.passcode.is_some() {
+                    credentials.push(("passcode", REDACTED.into()));
+                }
+                if mfa.passcode_in_password {
+                    credentials.push(("passcodeInPassword", "true".into()));
+                }
+                Some("username_password_mfa")
+            }
+            AuthStrategy::ExternalBrowser(_) => Some("externalbrowser"),
+            AuthStrategy::Okta(okta) => Some(okta.url()),
+            AuthStrategy::OAuth(_) => {
+                credentials.push(("token", REDACTED.into()));
+                Some("oauth")
+            }
+            AuthStrategy::ProgrammaticAccessToken(_) => {
+                credentials.push(("token", REDACTED.into()));
+                Some("programmatic_access_token")
+            }
+            #[cfg(feature = "auth-cert")]
+            AuthStrategy::Certificate(_) => {
+                credentials.push(("privateKey", REDACTED.into()));
+                Some("snowflake_jwt")
+            }
+            #[cfg(feature = "auth-oauth")]
+            AuthStrategy::OAuthClient(client) if client.is_client_credentials() => {
+                credentials.push(("oauthClientId", url::encode(&client.client_id)));
+                if client.client_secret.is_some() {
+                    credentials.push(("oauthClientSecret", REDACTED.into()));
+                }
+                if let Some(token_url) = &client.token_url {
+                    credentials.push(("oauthTokenRequestUrl", url::encode(token_url)));
+                }
+                if let Some(scope) = &client.scope {
+                    credentials.push(("oauthScope", url::encode(scope)));
+                }
+                Some("oauth_client_credentials")
+            }
+            // The browser and refresh token grants have no DSN representation
+            #[cfg(feature = "auth-oauth")]
+            AuthStrategy::OAuthClient(_) => None,
+            #[cfg(feature = "auth-wif")]
+            AuthStrategy::WorkloadIdentity(_) => Some("workload_identity"),
+            // User-defined authenticators have no DSN representation
+            AuthStrategy::Custom(_) => None,
+        };
+
+        if let Some(authenticator) = authenticator {
+            params.push(("authenticator", url::encode(authenticator)));
+        }
+        params.extend(credentials);
+    }
+}
+
+/// The credentials found in a DSN, turned into an `AuthStrategy` once every parameter is read.
+#[derive(Default)]
+struct DsnAuth {
+    authenticator: Option<String>,
+    password: Option<String>,
+    token: Option<String>,
+    passcode: Option<String>,
+    passcode_in_password: bool,
+    private_key: Option<String>,
+    oauth: DsnOAuthClient,
+}
+
+/// The `oauth_client_credentials` parameters, which only the `auth-oauth` feature reads.
+#[derive(Default)]
+#[cfg_attr(not(feature = "auth-oauth"), allow(dead_code))]
+struct DsnOAuthClient {
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    token_url: Option<String>,
+    scope: Option<String>,
+}
+
+impl DsnAuth {
+    fn strategy(self) -> Result<AuthStrategy, SnowflakeError> {
+        let authenticator = self.authenticator.as_deref().unwrap_or("snowflake");
+
+        let password = self.password.ok_or(error!(
+            "dsn is missing the password, expected user:password@"
+        ));
+        let token = self
+            .token
+            .ok_or(error!("dsn is missing the token parameter"));
+
+        match authenticator.to_lowercase().as_str() {
+            "snowflake" => Ok(AuthStrategy::Password(password?.into())),
+            "username_password_mfa" => {
+                let mut mfa = PasswordMfa::new(password?);
+                if let Some(passcode) = self.passcode {
+                    mfa = mfa.passcode(passcode);
+                }
+                if self.passcode_in_password {
+                    mfa = mfa.passcode_in_password();
+                }
+                Ok(AuthStrategy::PasswordMfa(mfa))
+            }
+            "externalbrowser" => Ok(AuthStrategy::ExternalBrowser(ExternalBrowser::new())),
+            "oauth" => Ok(AuthStrategy::OAuth(OAuthTokenProvider::from_token(token?))),
+            "programmatic_access_token" => Ok(AuthStrategy::ProgrammaticAccessToken(token?.into())),
+            #[cfg(feature = "auth-cert")]
+            "snowflake_jwt" => {
+                use base64::Engine;
+
+                // gosnowflake expects an unencrypted PKCS#8 key, DER encoded as URL safe base64
+                let key = self
+                    .private_key
+                    .ok_or(error!("dsn is missing the privateKey parameter"))?;
+                let key = key.trim_end_matches('=');
+                let der = this_errors!(
+                    "privateKey must be URL safe base64",
+                    base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(key)
+                );
+
+                Ok(AuthStrategy::Certificate(
+                    crate::auth::PrivateKey::from_der(der).into(),
+                ))
+            }
+            #[cfg(not(feature = "auth-cert"))]
+            "snowflake_jwt" => Err(error!("snowflake_jwt dsns need the auth-cert feature")),
+            #[cfg(feature = "auth-oauth")]
+            "oauth_client_credentials" => {
+                let client_id = self
+                    .oauth
+                    .client_id
+                    .ok_or(error!("dsn is missing the oauthClientId parameter"))?;
+                let client_secret = self
+                    .oauth
+                    .client_secret
+                    .ok_or(error!("dsn is missing the oauthClientSecret parameter"))?;
+
+                let mut client =
+                    crate::auth::OAuthClient::client_credentials(client_id, client_secret);
+                if let Some(token_url) = self.oauth.token_url {
+                    client = client.token_url(token_url);
+                }
+                if let Some(scope) = self.oauth.scope {
+                    client = client.scope(scope);
+                }
+                Ok(AuthStrategy::OAuthClient(client))
+            }
+            #[cfg(not(feature = "auth-oauth"))]
+            "oauth_client_credentials" => Err(error!(
+                "oauth_client_credentials dsns need the auth-oauth feature"
+            )),
+            okta if okta.starts_with("https://") => {
+                Ok(AuthStrategy::Okta(Okta::new(authenticator, password?)))
+            }
+            _ => Err(error!("unsupported authenticator in dsn", authenticator)),
+        }
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, SnowflakeError> {
+    match value.to_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(error!("dsn parameter must be true or false", key)),
+    }
+}
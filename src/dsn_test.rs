@@ -0,0 +1,204 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_parses_account_dsn() {
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "alice:p%40ss+word@myorg-dev/DEV_DB/PUBLIC?warehouse=DEV_WH&role=ANALYST",
+    )
+    .unwrap();
+
+    assert_eq!(opts.account_id, "myorg-dev");
+    assert_eq!(opts.username, "alice");
+    assert_eq!(opts.database.as_deref(), Some("DEV_DB"));
+    assert_eq!(opts.schema.as_deref(), Some("PUBLIC"));
+    assert_eq!(opts.warehouse.as_deref(), Some("DEV_WH"));
+    assert_eq!(opts.role.as_deref(), Some("ANALYST"));
+    assert_eq!(opts.host, None);
+    assert_eq!(opts.pool_size, 1);
+    assert!(matches!(&opts.strategy, AuthStrategy::Password(p) if p.expose() == "p@ss+word"));
+}
+
+#[test]
+fn test_parses_host_dsn() {
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "alice:secret@127.0.0.1:8080?account=myorg-dev&protocol=http",
+    )
+    .unwrap();
+
+    assert_eq!(opts.account_id, "myorg-dev");
+    assert_eq!(opts.host.as_deref(), Some("http://127.0.0.1:8080"));
+    assert_eq!(opts.database, None);
+}
+
+#[test]
+fn test_parses_authenticators() {
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "etl@myorg?authenticator=PROGRAMMATIC_ACCESS_TOKEN&token=pat-secret",
+    )
+    .unwrap();
+    assert!(matches!(
+        opts.strategy,
+        AuthStrategy::ProgrammaticAccessToken(_)
+    ));
+
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "etl:secret@myorg?authenticator=username_password_mfa&passcode=123456",
+    )
+    .unwrap();
+    assert!(
+        matches!(&opts.strategy, AuthStrategy::PasswordMfa(mfa) if mfa.passcode.as_ref().unwrap().expose() == "123456")
+    );
+
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "etl:secret@myorg?authenticator=https%3A%2F%2Fmyorg.okta.com",
+    )
+    .unwrap();
+    assert!(
+        matches!(&opts.strategy, AuthStrategy::Okta(okta) if okta.url() == "https://myorg.okta.com")
+    );
+
+    let err = SnowflakeConnectionOpts::from_dsn("etl@myorg?authenticator=oauth")
+        .expect_err("oauth without a token should fail");
+    assert!(err.to_string().contains("missing the token"));
+}
+
+#[test]
+fn test_builder_from_dsn_keeps_overrides() {
+    let opts = SnowflakeConnectionOptsBuilder::from_dsn("alice:secret@myorg")
+        .unwrap()
+        .pool_size(4)
+        .build()
+        .unwrap();
+
+    assert_eq!(opts.pool_size, 4);
+}
+
+#[test]
+fn test_other_parameters_are_session_parameters() {
+    let opts =
+        SnowflakeConnectionOpts::from_dsn("alice:secret@myorg?query_tag=nightly&TIMEZONE=UTC")
+            .unwrap();
+
+    assert_eq!(
+        opts.session_parameters.get("QUERY_TAG"),
+        Some(&serde_json::json!("nightly"))
+    );
+    assert_eq!(
+        opts.to_dsn(),
+        "alice:****@myorg?QUERY_TAG=nightly&TIMEZONE=UTC"
+    );
+}
+
+#[test]
+fn test_driver_only_parameters_are_ignored() {
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "alice:secret@myorg?loginTimeout=30&ocspFailOpen=true&tracing=debug&QUERY_TAG=etl",
+    )
+    .unwrap();
+
+    assert_eq!(opts.session_parameters.len(), 1);
+    assert!(opts.session_parameters.contains_key("QUERY_TAG"));
+}
+
+#[test]
+fn test_parses_hostname_address() {
+    let opts =
+        SnowflakeConnectionOpts::from_dsn("alice:secret@myorg-dev.snowflakecomputing.com/DEV_DB")
+            .unwrap();
+    assert_eq!(opts.account_id, "myorg-dev");
+    assert_eq!(
+        opts.host.as_deref(),
+        Some("https://myorg-dev.snowflakecomputing.com")
+    );
+    assert_eq!(opts.database.as_deref(), Some("DEV_DB"));
+
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "alice:secret@xy12345.us-east-1.snowflakecomputing.com:443",
+    )
+    .unwrap();
+    assert_eq!(opts.account_id, "xy12345.us-east-1");
+    assert_eq!(
+        opts.host.as_deref(),
+        Some("https://xy12345.us-east-1.snowflakecomputing.com:443")
+    );
+}
+
+#[test]
+fn test_region_is_appended_to_the_account() {
+    let opts = SnowflakeConnectionOpts::from_dsn("alice:secret@xy12345?region=us-east-1").unwrap();
+    assert_eq!(opts.account_id, "xy12345.us-east-1");
+
+    let opts = SnowflakeConnectionOpts::from_dsn("alice:secret@xy12345?region=us-west-2").unwrap();
+    assert_eq!(opts.account_id, "xy12345");
+}
+
+#[test]
+fn test_to_dsn_redacts_secrets() {
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "alice:hunter2@127.0.0.1:8080/DEV_DB/PUBLIC?account=myorg-dev&protocol=http&warehouse=DEV_WH&role=ANALYST",
+    )
+    .unwrap();
+
+    let dsn = opts.to_dsn();
+    assert_eq!(
+        dsn,
+        "alice:****@127.0.0.1:8080/DEV_DB/PUBLIC?account=myorg-dev&protocol=http&warehouse=DEV_WH&role=ANALYST"
+    );
+
+    let opts = SnowflakeConnectionOpts::from_dsn(
+        "etl@myorg?authenticator=programmatic_access_token&token=pat-secret",
+    )
+    .unwrap();
+
+    let dsn = opts.to_dsn();
+    assert!(!dsn.contains("pat-secret"));
+    assert_eq!(
+        dsn,
+        "etl@myorg?authenticator=programmatic_access_token&token=****"
+    );
+}
+
+#[test]
+fn test_to_dsn_round_trips_without_secrets() {
+    let dsn = "bob@myorg/My%20DB?role=ANALYST&application=nightly-etl&authenticator=externalbrowser&clientSessionKeepAlive=true&clientSessionKeepAliveHeartbeatFrequency=900";
+    let opts = SnowflakeConnectionOpts::from_dsn(dsn).unwrap();
+
+    assert_eq!(opts.database.as_deref(), Some("My DB"));
+    assert_eq!(opts.application, "nightly-etl");
+    assert_eq!(opts.to_dsn(), dsn);
+}
+
+#[cfg(feature = "auth-oauth")]
+#[test]
+fn test_oauth_client_credentials_round_trips() {
+    let dsn = "etl@myorg?authenticator=oauth_client_credentials&oauthClientId=etl-client&oauthClientSecret=client-secret&oauthTokenRequestUrl=https%3A%2F%2Fidp.example.com%2Ftoken&oauthScope=session%3Arole%3AETL";
+    let opts = SnowflakeConnectionOpts::from_dsn(dsn).unwrap();
+
+    let AuthStrategy::OAuthClient(client) = &opts.strategy else {
+        panic!("expected the oauth client credentials strategy");
+    };
+    assert_eq!(client.client_id, "etl-client");
+    assert_eq!(
+        client
+            .client_secret
+            .as_ref()
+            .map(crate::auth::Secret::expose),
+        Some("client-secret")
+    );
+    assert_eq!(
+        client.token_url.as_deref(),
+        Some("https://idp.example.com/token")
+    );
+    assert_eq!(client.scope.as_deref(), Some("session:role:ETL"));
+    assert!(opts.session_parameters.is_empty());
+
+    let redacted = dsn.replace("client-secret", "****");
+    assert_eq!(opts.to_dsn(), redacted);
+    assert_eq!(
+        SnowflakeConnectionOpts::from_dsn(&redacted)
+            .unwrap()
+            .to_dsn(),
+        redacted
+    );
+}
@@ -1,21 +1,129 @@
 use std::error::Error;
 use std::fmt::{Debug, Display};
 
+use crate::auth::LoginError;
+
+#[cfg(test)]
+#[path = "./errors_test.rs"]
+mod errors_test;
+
+// Values following these keys are masked in error messages, e.g. `"token": "..."` or `password=...`
+static SENSITIVE_KEYS: [&str; 7] = [
+    "token",
+    "password",
+    "passcode",
+    "secret",
+    "proof_key",
+    "code_verifier",
+    "assertion",
+];
+static REDACTED: &str = "[REDACTED]";
+
 #[derive(Clone)]
 pub struct SnowflakeError {
     trace: String,
     message: String,
     underlying_error: Option<String>,
+    login_error: Option<Box<LoginError>>,
 }
 
 impl SnowflakeError {
     pub(crate) fn new(trace: String, message: String, underlying: Option<String>) -> Self {
         Self {
             trace,
-            message,
+            message: redact(message),
             underlying_error: underlying,
+            login_error: None,
         }
     }
+
+    pub(crate) fn with_login_error(mut self, login_error: LoginError) -> Self {
+        self.login_error = Some(Box::new(login_error));
+        self
+    }
+
+    /// Why Snowflake rejected the login, if this error comes from a failed login.
+    pub fn login_error(&self) -> Option<&LoginError> {
+        self.login_error.as_deref()
+    }
+}
+
+/// Masks credentials that made their way into a message, usually through a response body or URL.
+fn redact(message: String) -> String {
+    let lower = message.to_ascii_lowercase();
+    let lower = lower.as_bytes();
+    let bytes = message.as_bytes();
+
+    let mut redacted = String::with_capacity(message.len());
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let Some(key) = SENSITIVE_KEYS
+            .iter()
+            .find(|key| lower[i..].starts_with(key.as_bytes()))
+        else {
+            i += 1;
+            continue;
+        };
+
+        match secret_value(bytes, i + key.len()) {
+            Some((start, end)) => {
+                redacted.push_str(&message[copied..start]);
+                redacted.push_str(REDACTED);
+                copied = end;
+                i = end;
+            }
+            None => i += key.len(),
+        }
+    }
+
+    redacted.push_str(&message[copied..]);
+    redacted
+}
+
+/// Finds the value assigned to the key ending at `i`, as `(start, end)` byte offsets.
+/// Values after `:` are only matched when quoted, so plain prose like "token: expired" is kept.
+fn secret_value(bytes: &[u8], mut i: usize) -> Option<(usize, usize)> {
+    let is_quote = |b: u8| matches!(b, b'"' | b'\'' | b'\\');
+
+    // The rest of the key, e.g. `masterToken` or `ID_TOKEN`, and its closing quotes
+    while bytes
+        .get(i)
+        .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_')
+    {
+        i += 1;
+    }
+    while bytes
+        .get(i)
+        .is_some_and(|b| is_quote(*b) || b.is_ascii_whitespace())
+    {
+        i += 1;
+    }
+
+    let separator = *bytes.get(i).filter(|b| matches!(b, b'=' | b':'))?;
+    i += 1;
+
+    while bytes.get(i).is_some_and(|b| b.is_ascii_whitespace()) {
+        i += 1;
+    }
+
+    let quoted = bytes.get(i).is_some_and(|b| is_quote(*b));
+    if !quoted && separator == b':' {
+        return None;
+    }
+    while bytes.get(i).is_some_and(|b| is_quote(*b)) {
+        i += 1;
+    }
+
+    let start = i;
+    while bytes.get(i).is_some_and(|b| {
+        !is_quote(*b) && !b.is_ascii_whitespace() && !matches!(b, b'&' | b',' | b';' | b'}')
+    }) {
+        i += 1;
+    }
+
+    (i > start).then_some((start, i))
 }
 
 impl Error for SnowflakeError {}
@@ -0,0 +1,41 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn redacts_json_credentials() {
+    let err =
+        error!(r#"{"data":{"TOKEN":"eyJhbGciOi","masterToken":"ver:1-abc","PASSWORD":"hunter2"}}"#);
+    let message = err.to_string();
+
+    assert!(!message.contains("eyJhbGciOi"), "{message}");
+    assert!(!message.contains("ver:1-abc"), "{message}");
+    assert!(!message.contains("hunter2"), "{message}");
+    assert!(message.contains(r#""TOKEN":"[REDACTED]""#), "{message}");
+}
+
+#[test]
+fn redacts_debug_formatted_credentials() {
+    let err = error!("login failed", r#"{"token": "abc123"}"#);
+
+    assert!(!err.to_string().contains("abc123"), "{err}");
+}
+
+#[test]
+fn redacts_urls_and_headers() {
+    let err = error!(
+        "https://okta.example.com/sso?onetimetoken=s3cr3t&RelayState=/ Authorization: Snowflake Token=\"session\""
+    );
+    let message = err.to_string();
+
+    assert!(!message.contains("s3cr3t"), "{message}");
+    assert!(!message.contains("\"session\""), "{message}");
+    assert!(message.contains("RelayState=/"), "{message}");
+}
+
+#[test]
+fn keeps_messages_without_credentials() {
+    let message = "failed to decrypt token cache, the key may have changed. token: expired";
+    let err = error!(message.to_string());
+
+    assert!(err.to_string().contains(message), "{err}");
+}
@@ -6,10 +6,11 @@ use serde::Deserialize;
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub(crate) struct AuthResponseSessionInfo {
-    pub database_name: String,
-    pub schema_name: String,
-    pub warehouse_name: String,
-    pub role_name: String,
+    // Null when the user has no default, or it doesn't exist
+    pub database_name: Option<String>,
+    pub schema_name: Option<String>,
+    pub warehouse_name: Option<String>,
+    pub role_name: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -64,3 +65,21 @@ pub(crate) enum LoginResponse {
     Success(super::GenericResponse<AuthData>),
     Failure(super::ErrorResponse),
 }
+
+// Returned by /session/authenticator-request, used to start SSO flows
+// https://github.com/snowflakedb/gosnowflake/blob/master/auth.go#L254
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub(crate) struct AuthenticatorRequestData {
+    pub token_url: Option<String>,
+    pub sso_url: Option<String>,
+    pub proof_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum AuthenticatorRequestResponse {
+    Success(super::GenericResponse<AuthenticatorRequestData>),
+    Failure(super::ErrorResponse),
+}
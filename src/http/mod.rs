@@ -1,21 +1,26 @@
 use std::{
     collections::HashMap,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
-use crate::{error, http::client::SnowflakeHttpClient, this_errors};
+use crate::{
+    error,
+    http::client::{HttpMethod, HttpRequest, SnowflakeHttpClient},
+    this_errors,
+};
 use derive_builder::Builder;
 use serde::{Deserialize, de::DeserializeOwned};
 use serde_json::Value;
 
-use crate::{SnowflakeError, connection::Connection};
+use crate::{SnowflakeError, auth::Secret, connection::Connection};
 
 pub mod client;
 pub mod close;
 pub mod login;
 mod macros;
 pub mod renew;
-mod url;
+pub(crate) mod retry;
+pub(crate) mod url;
 
 pub(crate) use macros::params;
 
@@ -39,9 +44,10 @@ pub(crate) struct GenericResponse<T> {
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct ErrorData {
-    authn_method: Option<String>,
-    error_code: Option<String>,
-    next_action: Option<String>,
+    pub(crate) authn_method: Option<String>,
+    pub(crate) error_code: Option<String>,
+    pub(crate) next_action: Option<String>,
+    pub(crate) in_flight_ctx: Option<String>,
 }
 
 pub(crate) type ErrorResponse = GenericResponse<ErrorData>;
@@ -62,30 +68,41 @@ pub(crate) struct Request<C: SnowflakeHttpClient + Clone> {
     full_url: Option<String>,
 
     #[builder(setter(into, strip_option), default = None)]
-    auth_token: Option<String>,
+    auth_token: Option<Secret>,
+
+    /// Sent as `requestId`. Retries of a request reuse its id, so Snowflake doesn't run it twice.
+    #[builder(setter(into, strip_option), default = None)]
+    request_id: Option<String>,
+
+    /// Retry connection errors, `429` and `5xx` responses with the connection's `RetryPolicy`.
+    #[builder(default = false)]
+    retry: bool,
+}
+
+/// Why a request is being sent again, sent as `retryCount` and `retryReason` like the official drivers.
+struct RetryContext {
+    count: u32,
+    // The HTTP status of the failed attempt, or 0 for a connection error
+    reason: u16,
 }
 
 impl<C: SnowflakeHttpClient + Clone> Request<C> {
-    fn build_url(&self) -> Result<String, SnowflakeError> {
+    fn build_url(
+        &self,
+        request_id: &str,
+        retry: Option<&RetryContext>,
+    ) -> Result<String, SnowflakeError> {
         if let Some(url) = self.full_url.as_deref() {
             Ok(url.to_string())
         } else {
-            let conn_opts = self.connection.get_opts();
             let pathname = if let Some(path) = self.path.as_deref() {
-                Ok(if let Some(host) = conn_opts.host.as_deref() {
-                    format!("{}{}", host, path)
-                } else {
-                    format!(
-                        "https://{}.snowflakecomputing.com{}",
-                        conn_opts.account_id, path
-                    )
-                })
+                Ok(format!("{}{}", self.connection.base_url(), path))
             } else {
                 Err(error!("either path or full_url needs to be set on Request"))
             }?;
 
             let mut params = params!(
-                ("requestId", uuid::Uuid::new_v4().to_string()),
+                ("requestId", request_id),
                 ("requestGuid", uuid::Uuid::new_v4().to_string()),
                 (
                     "clientStartTime",
@@ -94,7 +111,9 @@ impl<C: SnowflakeHttpClient + Clone> Request<C> {
                         .expect("time somehow went backwards")
                         .as_secs()
                         .to_string()
-                )
+                ),
+                ("retryCount", retry.map(|r| r.count.to_string())),
+                ("retryReason", retry.map(|r| r.reason.to_string()))
             );
 
             if let Some(p) = self.params.as_deref() {
@@ -110,34 +129,98 @@ impl<C: SnowflakeHttpClient + Clone> Request<C> {
         }
     }
 
-    pub async fn post<T: DeserializeOwned>(
-        self,
-        body: serde_json::Value,
-    ) -> Result<T, SnowflakeError> {
-        let url = self.build_url()?;
-        let mut headers = self.headers;
-
-        if let Some(token) = self.auth_token {
-            headers.insert(
+    /// Sends the request, retrying it according to the connection's `RetryPolicy` if `retry` is set.
+    async fn send(mut self, method: HttpMethod, body: Vec<u8>) -> Result<Vec<u8>, SnowflakeError> {
+        if let Some(token) = self.auth_token.take() {
+            self.headers.insert(
                 "Authorization".into(),
-                format!("Snowflake Token=\"{}\"", &token),
+                format!("Snowflake Token=\"{}\"", token.expose()),
             );
         }
 
-        headers.insert("content-type".into(), "application/json".into());
+        let request_id = self
+            .request_id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        // Waiting between attempts needs a runtime
+        let runtime = match self.retry {
+            true => self.connection.runtime(),
+            false => None,
+        };
+        // Only requests to Snowflake itself can fail over, not e.g. chunks from cloud storage
+        let fails_over = self.retry && self.full_url.is_none();
+        let opts = self.connection.get_opts();
+        let started = Instant::now();
+
+        let mut retry: Option<RetryContext> = None;
+        let mut failovers = 0;
+
+        loop {
+            let host = self.connection.active_host();
+            let request = HttpRequest {
+                method,
+                url: self.build_url(&request_id, retry.as_ref())?,
+                headers: self.headers.clone(),
+                body: body.clone(),
+            };
+
+            let resp = self.connection.get_client().send(request).await;
+
+            let (status, retry_after, err) = match resp {
+                Ok(resp) if self.retry && resp.status.is_some_and(retry::is_retryable_status) => (
+                    resp.status,
+                    resp.header("Retry-After")
+                        .and_then(retry::parse_retry_after),
+                    error!(
+                        "request failed with a retryable status",
+                        resp.status.unwrap_or_default()
+                    ),
+                ),
+                Ok(resp) => return Ok(resp.body),
+                Err(err) => (None, None, error!("failed to make request", err)),
+            };
+
+            let count = retry.as_ref().map_or(0, |r| r.count) + 1;
+            let reason = status.unwrap_or(0);
+
+            // Try every other host once straight away, before backing off
+            if fails_over
+                && failovers + 1 < self.connection.host_count()
+                && retry::should_fail_over(status)
+            {
+                self.connection.fail_over(host);
+                failovers += 1;
+                retry = Some(RetryContext { count, reason });
+                continue;
+            }
+
+            let delay = opts
+                .retry_policy
+                .next_delay(count, retry_after, started.elapsed());
+
+            let (Some(runtime), Some(delay)) = (runtime.as_ref(), delay) else {
+                return Err(err);
+            };
+
+            runtime.sleep(delay).await;
+            retry = Some(RetryContext { count, reason });
+        }
+    }
+
+    pub async fn post<T: DeserializeOwned>(
+        mut self,
+        body: serde_json::Value,
+    ) -> Result<T, SnowflakeError> {
+        self.headers
+            .insert("content-type".into(), "application/json".into());
 
         let body_bytes = this_errors!(
             "failed to serialise body as json",
             serde_json::to_vec(&body)
         );
 
-        let resp = this_errors!(
-            "failed to make request",
-            self.connection
-                .get_client()
-                .post(url.as_str(), body_bytes, headers)
-                .await
-        );
+        let resp = self.send(HttpMethod::Post, body_bytes).await?;
 
         let resp_as_json = this_errors!(
             "failed to parse response as json",
@@ -147,24 +230,20 @@ impl<C: SnowflakeHttpClient + Clone> Request<C> {
         Ok(resp_as_json)
     }
 
-    pub async fn get<T: DeserializeOwned>(self) -> Result<T, SnowflakeError> {
-        let url = self.build_url()?;
-        let mut headers = self.headers;
+    /// Posts `params` as a `application/x-www-form-urlencoded` body, as OAuth token endpoints expect.
+    #[cfg(feature = "auth-oauth")]
+    pub async fn post_form<T: DeserializeOwned>(
+        mut self,
+        params: &[(String, String)],
+    ) -> Result<T, SnowflakeError> {
+        self.headers.insert(
+            "content-type".into(),
+            "application/x-www-form-urlencoded".into(),
+        );
 
-        if let Some(token) = self.auth_token {
-            headers.insert(
-                "Authorization".into(),
-                format!("Snowflake Token=\"{}\"", &token),
-            );
-        }
+        let body = this_errors!("failed to encode form body", url::encode_form(params));
 
-        let resp = this_errors!(
-            "failed to make request",
-            self.connection
-                .get_client()
-                .get(url.as_str(), headers)
-                .await
-        );
+        let resp = self.send(HttpMethod::Post, body.into_bytes()).await?;
 
         let resp_as_json = this_errors!(
             "failed to parse response as json",
@@ -174,25 +253,23 @@ impl<C: SnowflakeHttpClient + Clone> Request<C> {
         Ok(resp_as_json)
     }
 
-    pub async fn get_as_bytes(self) -> Result<Vec<u8>, SnowflakeError> {
-        let url = self.build_url()?;
-        let mut headers = self.headers;
-
-        if let Some(token) = self.auth_token {
-            headers.insert(
-                "Authorization".into(),
-                format!("Snowflake Token=\"{}\"", &token),
-            );
-        }
+    pub async fn get<T: DeserializeOwned>(self) -> Result<T, SnowflakeError> {
+        let resp = self.send(HttpMethod::Get, Vec::new()).await?;
 
-        let resp = this_errors!(
-            "failed to make request",
-            self.connection
-                .get_client()
-                .get(url.as_str(), headers)
-                .await
+        let resp_as_json = this_errors!(
+            "failed to parse response as json",
+            serde_json::from_slice::<T>(&resp)
         );
 
-        Ok(resp)
+        Ok(resp_as_json)
+    }
+
+    pub async fn get_as_bytes(self) -> Result<Vec<u8>, SnowflakeError> {
+        self.send(HttpMethod::Get, Vec::new()).await
+    }
+
+    #[cfg(feature = "auth-wif")]
+    pub async fn put_as_bytes(self, body: Vec<u8>) -> Result<Vec<u8>, SnowflakeError> {
+        self.send(HttpMethod::Put, body).await
     }
 }
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+#[cfg(test)]
+#[path = "./retry_test.rs"]
+mod retry_test;
+
+/// How login, query and result chunk requests are retried when they fail with a connection error,
+/// `429 Too Many Requests` or a `5xx` status.
+///
+/// Retries wait with exponential backoff and jitter, or as long as a `Retry-After` header asks, up
+/// to `max_backoff`.
+/// Waiting needs a runtime, so without the `tokio` feature or `runtime()` requests aren't retried.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub(crate) max_attempts: u32,
+    pub(crate) initial_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+    pub(crate) deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(16),
+            deadline: Some(Duration::from_secs(300)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Never retry, every request is attempted once.
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// How many times a request is sent at most, including the first attempt.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// The wait before the first retry, doubled for every retry after it.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// The longest wait between two attempts, before jitter. Longer `Retry-After`s are cut short.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Stop retrying once a retry would start after this much time since the first attempt.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn no_deadline(mut self) -> Self {
+        self.deadline = None;
+        self
+    }
+
+    /// How long to wait before sending attempt `attempt + 1`, or `None` to give up.
+    pub(crate) fn next_delay(
+        &self,
+        attempt: u32,
+        retry_after: Option<Duration>,
+        elapsed: Duration,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        let delay = match retry_after {
+            Some(retry_after) => retry_after.min(self.max_backoff),
+            None => jitter(self.backoff(attempt)),
+        };
+
+        match self.deadline {
+            Some(deadline) if elapsed + delay > deadline => None,
+            _ => Some(delay),
+        }
+    }
+
+    /// The backoff before jitter for the retry following `attempt`.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Throttling, or a server or gateway error that usually goes away on its own.
+pub(crate) fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 429 | 500..=599)
+}
+
+/// Whether a failure means the endpoint itself is down, so another host should be tried.
+/// `status` is `None` for connection errors.
+pub(crate) fn should_fail_over(status: Option<u16>) -> bool {
+    matches!(status, None | Some(502..=504))
+}
+
+/// Parses a `Retry-After` header given in seconds. HTTP dates aren't supported and are ignored.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+// "Equal jitter", so concurrent clients spread out but still back off
+fn jitter(backoff: Duration) -> Duration {
+    let half = backoff / 2;
+    half + half.mul_f64(fastrand::f64())
+}
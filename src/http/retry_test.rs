@@ -0,0 +1,74 @@
+#[cfg(test)]
+use super::*;
+
+#[test]
+fn test_backoff_doubles_up_to_max() {
+    let policy = RetryPolicy::new()
+        .initial_backoff(Duration::from_secs(1))
+        .max_backoff(Duration::from_secs(5));
+
+    assert_eq!(policy.backoff(1), Duration::from_secs(1));
+    assert_eq!(policy.backoff(2), Duration::from_secs(2));
+    assert_eq!(policy.backoff(3), Duration::from_secs(4));
+    assert_eq!(policy.backoff(4), Duration::from_secs(5));
+    assert_eq!(policy.backoff(40), Duration::from_secs(5));
+}
+
+#[test]
+fn test_next_delay_is_jittered_backoff() {
+    let policy = RetryPolicy::new().initial_backoff(Duration::from_secs(4));
+
+    for _ in 0..20 {
+        let delay = policy.next_delay(1, None, Duration::ZERO).unwrap();
+        assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4));
+    }
+}
+
+#[test]
+fn test_next_delay_honours_retry_after() {
+    let policy = RetryPolicy::new().max_backoff(Duration::from_secs(60));
+
+    assert_eq!(
+        policy.next_delay(1, Some(Duration::from_secs(30)), Duration::ZERO),
+        Some(Duration::from_secs(30))
+    );
+    // A huge Retry-After doesn't hang the request
+    assert_eq!(
+        policy.next_delay(1, Some(Duration::from_secs(86400)), Duration::ZERO),
+        Some(Duration::from_secs(60))
+    );
+}
+
+#[test]
+fn test_next_delay_gives_up() {
+    let policy = RetryPolicy::new()
+        .max_attempts(3)
+        .deadline(Duration::from_secs(10));
+
+    assert!(policy.next_delay(2, None, Duration::ZERO).is_some());
+    assert_eq!(policy.next_delay(3, None, Duration::ZERO), None);
+    assert_eq!(
+        policy.next_delay(1, Some(Duration::from_secs(5)), Duration::from_secs(6)),
+        None
+    );
+    assert_eq!(
+        RetryPolicy::none().next_delay(1, None, Duration::ZERO),
+        None
+    );
+}
+
+#[test]
+fn test_retryable_statuses() {
+    for status in [429, 500, 502, 503, 504] {
+        assert!(is_retryable_status(status), "{status} should be retried");
+    }
+    for status in [200, 400, 401, 403, 404, 408] {
+        assert!(
+            !is_retryable_status(status),
+            "{status} shouldn't be retried"
+        );
+    }
+
+    assert_eq!(parse_retry_after(" 3 "), Some(Duration::from_secs(3)));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+}
@@ -28,6 +28,20 @@ pub fn construct_url(
     Ok(url)
 }
 
+/// Encodes `params` as an `application/x-www-form-urlencoded` body.
+#[cfg(feature = "auth-oauth")]
+pub fn encode_form(params: &[(String, String)]) -> Result<String, std::fmt::Error> {
+    let query = construct_url("", params)?;
+    Ok(query.trim_start_matches('?').to_string())
+}
+
+/// Percent-encodes everything but unreserved characters, for use in any part of a URL.
+pub fn encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    encode_into(&mut encoded, input).expect("writing to a String can't fail");
+    encoded
+}
+
 fn encode_into(buffer: &mut String, input: &str) -> Result<(), std::fmt::Error> {
     for b in input.as_bytes() {
         match b {
@@ -42,3 +56,82 @@ fn encode_into(buffer: &mut String, input: &str) -> Result<(), std::fmt::Error>
 
     Ok(())
 }
+
+/// Decodes a percent-encoded string. `+` is treated as a space, as used in query strings and form bodies.
+pub fn decode(input: &str) -> Result<String, std::string::FromUtf8Error> {
+    decode_with(input, true)
+}
+
+/// Decodes a percent-encoded path or userinfo component, where `+` is a literal `+`.
+pub fn decode_component(input: &str) -> Result<String, std::string::FromUtf8Error> {
+    decode_with(input, false)
+}
+
+fn decode_with(input: &str, plus_as_space: bool) -> Result<String, std::string::FromUtf8Error> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' if plus_as_space => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+                if let Some(b) = hex {
+                    decoded.push(b);
+                    i += 2;
+                } else {
+                    decoded.push(b'%');
+                }
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+
+    String::from_utf8(decoded)
+}
+
+/// Splits a query string (without the leading `?`) into decoded key value pairs.
+pub fn parse_query(query: &str) -> Result<Vec<(String, String)>, std::string::FromUtf8Error> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((decode(key)?, decode(value)?))
+        })
+        .collect()
+}
+
+/// Returns the scheme, host and port of a URL, filling in the default port for http and https.
+pub fn origin(url: &str) -> Option<(String, String, u16)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let scheme = scheme.to_ascii_lowercase();
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority
+        .rsplit_once('@')
+        .map(|(_, a)| a)
+        .unwrap_or(authority);
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse::<u16>().ok()?),
+        None => (
+            authority,
+            match scheme.as_str() {
+                "http" => 80,
+                "https" => 443,
+                _ => return None,
+            },
+        ),
+    };
+
+    if host.is_empty() {
+        return None;
+    }
+
+    Some((scheme, host.to_ascii_lowercase(), port))
+}
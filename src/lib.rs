@@ -1,14 +1,21 @@
+pub(crate) mod account;
 pub mod auth;
+#[cfg(feature = "connections-toml")]
+pub(crate) mod config;
 pub(crate) mod connection;
 pub(crate) mod driver;
+pub(crate) mod dsn;
 pub(crate) mod errors;
 pub(crate) mod executor;
 pub(crate) mod http;
+pub(crate) mod runtime;
 pub(crate) mod transaction;
 pub(crate) mod utils;
 
 pub(crate) use errors::{SnowflakeError, error, this_errors};
 
+pub use account::AccountIdentifier;
+pub use auth::session::SessionInfo;
 pub use connection::{
     SnowflakeConnection, SnowflakeConnectionOpts, SnowflakeConnectionOptsBuilder, SnowflakePool,
 };
@@ -53,10 +60,17 @@ pub type ArrowQuery<C = DefaultClient> = AQ<C>;
 #[cfg(feature = "arrow")]
 pub type ArrowQueryResult<C = DefaultClient> = AQR<C>;
 
-pub use http::client::SnowflakeHttpClient;
+pub use http::{
+    client::{HttpClientConfig, HttpMethod, HttpRequest, HttpResponse, SnowflakeHttpClient},
+    retry::RetryPolicy,
+};
 
 pub use executor::Executor;
 
+pub use runtime::SnowflakeRuntime;
+#[cfg(feature = "tokio")]
+pub use runtime::TokioRuntime;
+
 #[cfg(feature = "arrow")]
 use crate::driver::protocols::{ArrowQuery as AQ, ArrowQueryResult as AQR};
 
@@ -65,3 +79,6 @@ pub use crate::driver::protocols::{ArrowDescribeResult, ArrowProtocol};
 
 #[cfg(test)]
 mod tests {}
+
+#[cfg(test)]
+pub(crate) mod test_utils;
@@ -0,0 +1,30 @@
+use std::{fmt::Debug, time::Duration};
+
+use futures_util::future::BoxFuture;
+
+/// The async runtime background tasks (like `client_session_keep_alive`) run on.
+///
+/// The crate doesn't depend on a runtime itself. Enable the `tokio` feature for `TokioRuntime`, or
+/// implement this for the runtime you use.
+pub trait SnowflakeRuntime: Send + Sync + Debug {
+    /// Runs `future` to completion in the background.
+    fn spawn(&self, future: BoxFuture<'static, ()>);
+
+    /// Completes after `duration` has passed.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "tokio")]
+impl SnowflakeRuntime for TokioRuntime {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        tokio::spawn(future);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
@@ -0,0 +1,489 @@
+//! Local stand-ins for Snowflake and identity providers, so auth flows can be tested without a real account.
+
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use futures_util::future::BoxFuture;
+use serde_json::{Value, json};
+
+use crate::{
+    SnowflakeConnectionOpts, SnowflakeConnectionOptsBuilder, SnowflakeError,
+    auth::AuthStrategy,
+    error,
+    http::{
+        client::{HttpMethod, HttpRequest, HttpResponse, SnowflakeHttpClient},
+        url,
+    },
+    runtime::SnowflakeRuntime,
+    this_errors,
+};
+
+#[derive(Debug, Clone)]
+pub(crate) struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+
+    pub fn query_param(&self, key: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+pub(crate) struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn json(body: Value) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: serde_json::to_vec(&body).unwrap(),
+        }
+    }
+
+    pub fn html(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".into(), "text/html".into())],
+            body: body.into().into_bytes(),
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+/// A tiny HTTP/1.1 server running on its own thread. Every request is recorded and answered by the handler.
+pub(crate) struct MockServer {
+    port: u16,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MockServer {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        {
+            let requests = requests.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let requests = requests.clone();
+                            let handler = handler.clone();
+                            std::thread::spawn(move || serve(stream, &requests, &*handler));
+                        }
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                            std::thread::sleep(Duration::from_millis(5));
+                        }
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+
+        Self {
+            port,
+            requests,
+            stop,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn requests_to(&self, path: &str) -> Vec<MockRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.path == path)
+            .collect()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn serve(mut stream: TcpStream, requests: &Mutex<Vec<MockRequest>>, handler: &Handler) {
+    stream.set_nonblocking(false).unwrap();
+    let (head, body) = match read_message(&mut stream) {
+        Ok(message) => message,
+        Err(_) => return,
+    };
+
+    let mut request_line = head.split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let headers = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let request = MockRequest {
+        method,
+        path: path.to_string(),
+        query: url::parse_query(query).unwrap_or_default(),
+        headers,
+        body,
+    };
+
+    requests.lock().unwrap().push(request.clone());
+    let response = handler(&request);
+
+    let mut out = format!("HTTP/1.1 {} MOCK\r\n", response.status);
+    for (k, v) in response.headers.iter() {
+        out.push_str(&format!("{k}: {v}\r\n"));
+    }
+    out.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+
+    let _ = stream.write_all(out.as_bytes());
+    let _ = stream.write_all(&response.body);
+}
+
+/// Reads a HTTP message, returning its head (start line and headers) and body.
+fn read_message(stream: &mut TcpStream) -> std::io::Result<(String, Vec<u8>)> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let read = stream.read(&mut chunk)?;
+        if read == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok());
+
+    match content_length {
+        Some(length) => {
+            while buffer.len() < header_end + length {
+                let read = stream.read(&mut chunk)?;
+                if read == 0 {
+                    break;
+                }
+                buffer.extend_from_slice(&chunk[..read]);
+            }
+        }
+        None if !head.starts_with("HTTP/") => {}
+        None => {
+            stream.read_to_end(&mut buffer)?;
+        }
+    }
+
+    Ok((head, buffer.split_off(header_end)))
+}
+
+/// Sends a blocking HTTP/1.1 request. Only plain `http://` URLs are supported.
+pub(crate) fn send_request(
+    method: &str,
+    target: &str,
+    headers: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<(u16, Vec<u8>), SnowflakeError> {
+    let (head, body) = exchange(method, target, headers, body)?;
+    Ok((status_code(&head)?, body))
+}
+
+/// Sends a blocking HTTP/1.1 request, returning the head and body of the response.
+fn exchange(
+    method: &str,
+    target: &str,
+    headers: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<(String, Vec<u8>), SnowflakeError> {
+    let rest = target
+        .strip_prefix("http://")
+        .ok_or(error!("test client only supports http urls"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+
+    let mut stream = this_errors!("failed to connect", TcpStream::connect(authority));
+
+    let mut out = format!("{method} {path} HTTP/1.1\r\nHost: {authority}\r\n");
+    for (k, v) in headers.iter() {
+        out.push_str(&format!("{k}: {v}\r\n"));
+    }
+    out.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    ));
+
+    this_errors!("failed to write request", stream.write_all(out.as_bytes()));
+    this_errors!("failed to write request", stream.write_all(body));
+
+    Ok(this_errors!(
+        "failed to read response",
+        read_message(&mut stream)
+    ))
+}
+
+fn status_code(head: &str) -> Result<u16, SnowflakeError> {
+    head.split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or(error!("invalid status line"))
+}
+
+/// A `SnowflakeHttpClient` that only needs std, so tests don't depend on the `reqwest` feature.
+#[derive(Clone)]
+pub(crate) struct TestClient;
+
+impl SnowflakeHttpClient for TestClient {
+    fn new() -> Self {
+        TestClient
+    }
+
+    async fn get(
+        &self,
+        url: &str,
+        headers: HashMap<String, String>,
+    ) -> Result<Vec<u8>, SnowflakeError> {
+        send_request("GET", url, &headers, &[]).map(|(_, body)| body)
+    }
+
+    async fn post(
+        &self,
+        url: &str,
+        body: Vec<u8>,
+        headers: HashMap<String, String>,
+    ) -> Result<Vec<u8>, SnowflakeError> {
+        send_request("POST", url, &headers, &body).map(|(_, body)| body)
+    }
+
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse, SnowflakeError> {
+        let method = match request.method {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+        };
+        let (head, body) = exchange(method, &request.url, &request.headers, &request.body)?;
+
+        Ok(HttpResponse {
+            status: Some(status_code(&head)?),
+            headers: head
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect(),
+            body,
+        })
+    }
+}
+
+/// A `SnowflakeRuntime` that spawns on the test's tokio runtime and sleeps on a thread, so tests
+/// don't depend on the `tokio` feature.
+#[derive(Debug)]
+pub(crate) struct TestRuntime;
+
+impl SnowflakeRuntime for TestRuntime {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        tokio::spawn(future);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        let (tx, rx) = futures_channel::oneshot::channel::<()>();
+        std::thread::spawn(move || {
+            std::thread::sleep(duration);
+            let _ = tx.send(());
+        });
+
+        Box::pin(async move {
+            let _ = rx.await;
+        })
+    }
+}
+
+/// A successful `/session/v1/login-request` response.
+pub(crate) fn login_success() -> MockResponse {
+    login_success_with(json!({}))
+}
+
+/// A successful login response, with `extra` merged into its `data`.
+pub(crate) fn login_success_with(extra: Value) -> MockResponse {
+    let mut body = json!({
+        "data": {
+            "validityInSeconds": 3600,
+            "token": "session-token",
+            "masterValidityInSeconds": 14400,
+            "masterToken": "master-token",
+            "idTokenValidityInSeconds": 0,
+            "displayUserName": "TEST_USER",
+            "serverVersion": "9.0.0",
+            "firstLogin": false,
+            "mfaTokenValidityInSeconds": 0,
+            "remMeValidityInSeconds": 0,
+            "healthCheckInterval": 45,
+            "sessionId": 1234,
+            "parameters": [],
+            "sessionInfo": {
+                "databaseName": "TEST_DB",
+                "schemaName": "PUBLIC",
+                "warehouseName": "TEST_WH",
+                "roleName": "TEST_ROLE"
+            }
+        },
+        "code": null,
+        "message": null,
+        "success": true
+    });
+
+    if let (Some(data), Value::Object(extra)) = (body["data"].as_object_mut(), extra) {
+        data.extend(extra);
+    }
+
+    MockResponse::json(body)
+}
+
+/// A failed login, as Snowflake returns it.
+pub(crate) fn login_failure(code: &str, message: &str) -> MockResponse {
+    MockResponse::json(json!({
+        "data": {
+            "authnMethod": "PASSWORD",
+            "errorCode": code,
+            "nextAction": null
+        },
+        "code": code,
+        "message": message,
+        "success": false
+    }))
+}
+
+/// A `/queries/v1/query-request` response for `SELECT 1`.
+pub(crate) fn query_success() -> MockResponse {
+    query_success_with(json!({}))
+}
+
+/// A query response for `SELECT 1`, with `extra` merged into its `data`.
+pub(crate) fn query_success_with(extra: Value) -> MockResponse {
+    let mut body = json!({
+        "data": {
+            "rowtype": [
+                { "type": "fixed", "name": "1", "precision": 1, "scale": 0, "nullable": false }
+            ],
+            "rowset": [["1"]],
+            "total": 1,
+            "returned": 1,
+            "queryId": "01b2c3d4-0000-0000-0000-000000000001",
+            "queryResultFormat": "json",
+            "sqlState": "00000",
+            "numberOfBinds": 0,
+            "statementTypeId": 4096,
+            "version": 1
+        },
+        "code": null,
+        "message": null,
+        "success": true
+    });
+
+    if let (Some(data), Value::Object(extra)) = (body["data"].as_object_mut(), extra) {
+        data.extend(extra);
+    }
+
+    MockResponse::json(body)
+}
+
+/// A request rejected because of the session, e.g. `390112` for an expired session token.
+pub(crate) fn session_error(code: &str) -> MockResponse {
+    MockResponse::json(json!({
+        "data": null,
+        "code": code,
+        "message": "Your session has expired. Please login again.",
+        "success": false
+    }))
+}
+
+/// Connection options pointing at a local stand-in for Snowflake.
+pub(crate) fn test_opts(host: &str, strategy: AuthStrategy) -> SnowflakeConnectionOpts {
+    test_opts_builder(host, strategy)
+        .build()
+        .expect("failed to build test connection options")
+}
+
+/// The builder behind `test_opts`, for tests that need to set more options.
+pub(crate) fn test_opts_builder(
+    host: &str,
+    strategy: AuthStrategy,
+) -> SnowflakeConnectionOptsBuilder {
+    let mut builder = SnowflakeConnectionOptsBuilder::default();
+    builder
+        .account_id("TEST_ACCOUNT")
+        .username("TEST_USER")
+        .host(host)
+        .strategy(strategy)
+        .pool_size(1);
+    builder
+}
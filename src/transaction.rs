@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::{
     SnowflakeError,
-    auth::session::Session,
+    auth::session::{Session, SessionInfo},
     driver::{
         Protocol,
         primitives::row::Row,
@@ -48,6 +48,17 @@ impl<C: SnowflakeHttpClient, T: Protocol> SnowflakeTransaction<C, T> {
         }
     }
 
+    /// The session this transaction runs in, e.g. its ID and the role queries currently run as.
+    pub async fn session_info(&self) -> Result<SessionInfo, SnowflakeError> {
+        if let Some(existing) = self.session.as_ref() {
+            Ok(existing.lock().await.info.clone())
+        } else {
+            Err(error!(
+                "The underlying session for this transaction is dead."
+            ))
+        }
+    }
+
     pub async fn rollback(self) -> Result<(), SnowflakeError> {
         if let Some(existing) = self.session.as_ref() {
             let weak = Arc::downgrade(existing);
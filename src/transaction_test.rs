@@ -17,7 +17,7 @@ async fn test_tx() {
         .database(std::env::var("SF_DATABASE").unwrap())
         .schema(std::env::var("SF_SCHEMA").unwrap())
         .strategy(AuthStrategy::Password(
-            std::env::var("SF_PASSWORD").unwrap(),
+            std::env::var("SF_PASSWORD").unwrap().into(),
         ))
         .pool_size(1)
         .download_chunks_in_parallel(10 as usize)